@@ -3,7 +3,10 @@ use criterion::criterion_main;
 use criterion::BenchmarkId;
 use criterion::Criterion;
 use criterion::Throughput;
-use huffman_coding::huffman_encode;
+use huffman_coding::{
+    build_fast_decode_table, compress, decode_with_tree, frequency_counter, huffman_build,
+    huffman_decode, huffman_encode,
+};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 
@@ -21,11 +24,89 @@ pub fn bench_huffman_encode(ctx: &mut Criterion) {
             BenchmarkId::new("huffman_encode", data.len()),
             &data,
             |b, text| {
-                b.iter(|| huffman_encode(&text));
+                b.iter(|| huffman_encode(text));
             },
         );
     }
 }
 
-criterion_group!(benches, bench_huffman_encode);
+pub fn bench_huffman_decode(ctx: &mut Criterion) {
+    let mut group = ctx.benchmark_group("Huffman Decode");
+    let step = 1024;
+    for size in std::iter::successors(Some(step), |x| Some(x + step)).take(20) {
+        let data: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(size)
+            .map(|c| c as char)
+            .collect();
+        let (encoded, codes, _) = huffman_encode(&data);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("huffman_decode", data.len()),
+            &encoded,
+            |b, encoded| {
+                b.iter(|| huffman_decode(encoded, &codes, size).unwrap());
+            },
+        );
+    }
+}
+
+pub fn bench_frequency_counter(ctx: &mut Criterion) {
+    let mut group = ctx.benchmark_group("Frequency Counter");
+    let data: Vec<u8> = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(10 * 1024 * 1024)
+        .collect();
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("frequency_counter", |b| {
+        b.iter(|| frequency_counter(&data));
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("par_frequency_counter", |b| {
+        b.iter(|| huffman_coding::par_frequency_counter(&data));
+    });
+}
+
+pub fn bench_compress(ctx: &mut Criterion) {
+    let mut group = ctx.benchmark_group("Compress");
+    let size = 64 * 1024;
+    let alphanumeric: Vec<u8> = thread_rng().sample_iter(&Alphanumeric).take(size).collect();
+    let random: Vec<u8> = thread_rng()
+        .sample_iter(rand::distributions::Standard)
+        .take(size)
+        .collect();
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function("alphanumeric", |b| {
+        b.iter(|| compress(&alphanumeric));
+    });
+    // Uniform bytes are stored without coding.
+    group.bench_function("random", |b| {
+        b.iter(|| compress(&random));
+    });
+}
+
+pub fn bench_fast_decode(ctx: &mut Criterion) {
+    let mut group = ctx.benchmark_group("Fast Decode");
+    let size = 64 * 1024;
+    let data: Vec<u8> = thread_rng().sample_iter(&Alphanumeric).take(size).collect();
+    let model = huffman_build(&data);
+    let (encoded, _) = model.encode(&data).unwrap();
+    let table = build_fast_decode_table(model.codes(), 11);
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function("tree", |b| {
+        b.iter(|| decode_with_tree(&encoded, model.tree(), size).unwrap());
+    });
+    group.bench_function("table", |b| {
+        b.iter(|| table.decode(&encoded, size).unwrap());
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_huffman_encode,
+    bench_huffman_decode,
+    bench_frequency_counter,
+    bench_compress,
+    bench_fast_decode
+);
 criterion_main!(benches);
@@ -0,0 +1,218 @@
+// Adaptive Huffman coding with the FGK algorithm. Encoder and decoder start
+// from the same tree holding only the NYT ("not yet transmitted") node and
+// update it identically after every character, so no table is sent:
+//
+// | original length: u64 | bitstream |
+//
+// A character seen for the first time is written as the code of the NYT node
+// followed by its 8 raw bits; every other character as its current code.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    bitio::{BitReader, BitWriter},
+    DecodeError,
+};
+
+// Enough nodes for 256 leaves, 255 internal nodes and the NYT node.
+const MAX_NODES: usize = 2 * 256 + 1;
+const ROOT: usize = MAX_NODES - 1;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AdaptiveNode {
+    weight: usize,
+    parent: Option<usize>,
+    // Both children are set on internal nodes and neither on leaves.
+    children: Option<(usize, usize)>,
+    character: Option<u8>,
+}
+
+// Nodes are stored by their FGK number, so weights never decrease with the
+// index and the root is the last node.
+struct AdaptiveTree {
+    nodes: Vec<AdaptiveNode>,
+    leaves: [Option<usize>; 256],
+    nyt: usize,
+}
+
+impl AdaptiveTree {
+    fn new() -> Self {
+        AdaptiveTree {
+            nodes: vec![AdaptiveNode::default(); MAX_NODES],
+            leaves: [None; 256],
+            nyt: ROOT,
+        }
+    }
+
+    // Path from the root to `node`, 0 for left and 1 for right.
+    fn write_code(&self, mut node: usize, writer: &mut BitWriter) {
+        let mut code = Vec::new();
+        while let Some(parent) = self.nodes[node].parent {
+            let (left, _) = self.nodes[parent].children.unwrap();
+            code.push(node != left);
+            node = parent;
+        }
+        for &bit in code.iter().rev() {
+            writer.write_bit(bit);
+        }
+    }
+
+    // Swaps the subtrees numbered `a` and `b`, each keeping its position's
+    // parent.
+    fn swap(&mut self, a: usize, b: usize) {
+        let (parent_a, parent_b) = (self.nodes[a].parent, self.nodes[b].parent);
+        self.nodes.swap(a, b);
+        self.nodes[a].parent = parent_a;
+        self.nodes[b].parent = parent_b;
+        for node in [a, b] {
+            if let Some((left, right)) = self.nodes[node].children {
+                self.nodes[left].parent = Some(node);
+                self.nodes[right].parent = Some(node);
+            }
+            if let Some(c) = self.nodes[node].character {
+                self.leaves[c as usize] = Some(node);
+            }
+        }
+    }
+
+    fn update(&mut self, c: u8) {
+        let mut node = match self.leaves[c as usize] {
+            Some(leaf) => leaf,
+            None => {
+                // The NYT node becomes an internal node with a new NYT node on
+                // the left and the new leaf on the right.
+                let (nyt, leaf) = (self.nyt - 2, self.nyt - 1);
+                self.nodes[self.nyt].children = Some((nyt, leaf));
+                self.nodes[nyt] = AdaptiveNode {
+                    parent: Some(self.nyt),
+                    ..AdaptiveNode::default()
+                };
+                self.nodes[leaf] = AdaptiveNode {
+                    parent: Some(self.nyt),
+                    character: Some(c),
+                    ..AdaptiveNode::default()
+                };
+                self.leaves[c as usize] = Some(leaf);
+                self.nyt = nyt;
+                leaf
+            }
+        };
+
+        loop {
+            // Move the node to the highest number of its weight class before
+            // incrementing it, which keeps the sibling property.
+            let weight = self.nodes[node].weight;
+            let leader = (node + 1..MAX_NODES)
+                .take_while(|&i| self.nodes[i].weight == weight)
+                .last()
+                .unwrap_or(node);
+            if leader != node && Some(leader) != self.nodes[node].parent {
+                self.swap(node, leader);
+                node = leader;
+            }
+            self.nodes[node].weight += 1;
+            match self.nodes[node].parent {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Compresses `data` in a single pass with adaptive Huffman coding. Unlike
+/// [`compress`](crate::compress) no tree is stored in the output.
+pub fn adaptive_encode(data: &[u8]) -> Vec<u8> {
+    let mut tree = AdaptiveTree::new();
+    let mut writer = BitWriter::with_capacity(data.len() / 2);
+    for &c in data {
+        match tree.leaves[c as usize] {
+            Some(leaf) => tree.write_code(leaf, &mut writer),
+            None => {
+                tree.write_code(tree.nyt, &mut writer);
+                writer.write_bits(c as u64, 8);
+            }
+        }
+        tree.update(c);
+    }
+
+    let mut blob = (data.len() as u64).to_le_bytes().to_vec();
+    blob.extend(writer.finish().0);
+    blob
+}
+
+/// Restores data written by [`adaptive_encode`].
+pub fn adaptive_decode(blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (original_len, bitstream) = blob
+        .split_first_chunk::<8>()
+        .ok_or(DecodeError::BadHeader)?;
+    let original_len =
+        usize::try_from(u64::from_le_bytes(*original_len)).map_err(|_| DecodeError::BadHeader)?;
+
+    let mut tree = AdaptiveTree::new();
+    let mut reader = BitReader::new(bitstream);
+    // Every character takes at least one bit, so a larger length is bogus.
+    let mut decoded = Vec::with_capacity(original_len.min(bitstream.len() * 8));
+    for _ in 0..original_len {
+        let mut node = ROOT;
+        while let Some((left, right)) = tree.nodes[node].children {
+            let bit = reader.read_bit().ok_or(DecodeError::UnexpectedEof)?;
+            node = if bit { right } else { left };
+        }
+        let c = match tree.nodes[node].character {
+            Some(c) => c,
+            None => reader.read_bits(8).ok_or(DecodeError::UnexpectedEof)? as u8,
+        };
+        tree.update(c);
+        decoded.push(c);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_adaptive_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let skewed = (0..4096).map(|_| r.gen_range(0..4)).collect::<Vec<u8>>();
+        let all = (0..=255).cycle().take(1024).collect::<Vec<u8>>();
+        let inputs: [&[u8]; 7] = [b"", b"A", b"AAAA", b"AABCBAD", &random, &skewed, &all];
+        for data in inputs {
+            assert_eq!(adaptive_decode(&adaptive_encode(data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_encode_has_no_table() {
+        let blob = adaptive_encode(b"AABCBAD");
+        assert_eq!(&blob[..8], &7u64.to_le_bytes());
+        // The first character is sent raw right after the length, with an
+        // empty NYT code in front of it.
+        assert_eq!(blob[8], b'A');
+        // A(8) A(1) B(1+8) C(2+8) B(2) A(1) D(3+8) = 42 bits.
+        assert_eq!(blob.len(), 8 + 6);
+    }
+
+    #[test]
+    fn test_adaptive_encode_skewed_input_is_smaller() {
+        let data = [b"A".repeat(1000), b"B".repeat(10)].concat();
+        assert!(adaptive_encode(&data).len() < data.len() / 4);
+    }
+
+    #[test]
+    fn test_adaptive_decode_truncated() {
+        let blob = adaptive_encode(b"AABCBAD");
+        assert!(matches!(
+            adaptive_decode(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            adaptive_decode(&blob[..4]),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+}
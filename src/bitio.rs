@@ -0,0 +1,260 @@
+//! Bit-level reading and writing.
+//!
+//! Bits are packed MSB-first by default: the first bit written lands in the
+//! highest bit of the first byte. [`BitOrder::LsbFirst`] fills each byte from
+//! its lowest bit instead. When the number of bits is not a multiple of 8 the
+//! last byte is padded with zero bits.
+
+use alloc::vec::Vec;
+
+/// Order in which the bits of a stream fill each byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BitOrder {
+    /// The first bit goes in the highest bit of a byte, as in JPEG.
+    #[default]
+    MsbFirst,
+    /// The first bit goes in the lowest bit of a byte, as in DEFLATE.
+    LsbFirst,
+}
+
+impl BitOrder {
+    // Converts bytes filled MSB-first to this order.
+    fn arrange(self, mut bytes: Vec<u8>) -> Vec<u8> {
+        if self == BitOrder::LsbFirst {
+            bytes
+                .iter_mut()
+                .for_each(|byte| *byte = byte.reverse_bits());
+        }
+        bytes
+    }
+
+    // XORed with the index of a bit in its byte, counted from the highest
+    // bit, to get its shift from the lowest.
+    fn shift_mask(self) -> usize {
+        match self {
+            BitOrder::MsbFirst => 7,
+            BitOrder::LsbFirst => 0,
+        }
+    }
+}
+
+/// Accumulates bits and packs them into bytes.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    buffered: u32,
+    bit_len: usize,
+    order: BitOrder,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a writer with room for `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        BitWriter {
+            bytes: Vec::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Packs the bits in `order` instead of MSB-first.
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u64, 1);
+    }
+
+    /// Writes the low `count` bits of `value`, most significant first.
+    #[inline]
+    pub fn write_bits(&mut self, value: u64, count: u8) {
+        assert!(count <= 64, "Cannot write more than 64 bits at once");
+        if count > 32 {
+            self.write_bits(value >> 32, count - 32);
+            self.write_bits(value, 32);
+            return;
+        }
+        let value = value & ((1 << count) - 1);
+        self.buffer = self.buffer << count | value;
+        self.buffered += count as u32;
+        self.bit_len += count as usize;
+        while self.buffered >= 8 {
+            self.buffered -= 8;
+            self.bytes.push((self.buffer >> self.buffered) as u8);
+        }
+    }
+
+    /// Number of bits written so far, excluding padding.
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    /// Removes and returns the bytes that are already complete, keeping any
+    /// leftover bits for later writes.
+    #[cfg(feature = "std")]
+    pub(crate) fn take_bytes(&mut self) -> Vec<u8> {
+        self.order.arrange(core::mem::take(&mut self.bytes))
+    }
+
+    /// Pads the last byte and returns the bytes and the number of bits written.
+    pub fn finish(mut self) -> (Vec<u8>, usize) {
+        if self.buffered > 0 {
+            self.bytes.push((self.buffer << (8 - self.buffered)) as u8);
+        }
+        (self.order.arrange(self.bytes), self.bit_len)
+    }
+}
+
+/// Reads bits back from bytes packed by [`BitWriter`]. As an iterator it
+/// yields every remaining bit.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit_len: usize,
+    shift_mask: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Reads every bit of `bytes`, including any padding.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_bit_len(bytes, bytes.len() * 8)
+    }
+
+    /// Reads only the first `bit_len` bits of `bytes`.
+    pub fn with_bit_len(bytes: &'a [u8], bit_len: usize) -> Self {
+        BitReader {
+            bytes,
+            position: 0,
+            bit_len: bit_len.min(bytes.len() * 8),
+            shift_mask: BitOrder::MsbFirst.shift_mask(),
+        }
+    }
+
+    /// Reads bits packed in `order` instead of MSB-first.
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.shift_mask = order.shift_mask();
+        self
+    }
+
+    /// Number of bits read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        if self.position >= self.bit_len {
+            return None;
+        }
+        let byte = self.bytes[self.position / 8];
+        let bit = byte >> ((self.position % 8) ^ self.shift_mask) & 1 == 1;
+        self.position += 1;
+        Some(bit)
+    }
+
+    /// Reads `count` bits as a number, most significant first. Returns `None`
+    /// without consuming anything if fewer than `count` bits are left.
+    pub fn read_bits(&mut self, count: u8) -> Option<u64> {
+        assert!(count <= 64, "Cannot read more than 64 bits at once");
+        if self.bit_len - self.position < count as usize {
+            return None;
+        }
+        Some((0..count).fold(0, |acc, _| {
+            acc << 1 | self.read_bit().expect("Bit count was checked") as u64
+        }))
+    }
+}
+
+impl Iterator for BitReader<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        self.read_bit()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.bit_len - self.position;
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_bit_writer() {
+        let mut writer = BitWriter::new();
+        writer.write_bit(true);
+        writer.write_bits(0b011, 3);
+        writer.write_bits(0b1_0101_0101, 9);
+        assert_eq!(writer.bit_len(), 13);
+        assert_eq!(writer.finish(), (vec![0b1011_1010, 0b1010_1000], 13));
+
+        assert_eq!(BitWriter::new().finish(), (vec![], 0));
+    }
+
+    #[test]
+    fn test_bit_writer_wide_values() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(u64::MAX, 3);
+        writer.write_bits(0x0123_4567_89AB_CDEF, 64);
+        let (bytes, bit_len) = writer.finish();
+        assert_eq!(bit_len, 67);
+
+        let mut reader = BitReader::with_bit_len(&bytes, bit_len);
+        assert_eq!(reader.read_bits(3), Some(0b111));
+        assert_eq!(reader.read_bits(64), Some(0x0123_4567_89AB_CDEF));
+        assert_eq!(reader.read_bit(), None);
+    }
+
+    #[test]
+    fn test_bit_round_trip() {
+        let bits = [
+            true, false, true, true, false, false, false, true, true, false, true,
+        ];
+        let mut writer = BitWriter::new();
+        for &bit in &bits {
+            writer.write_bit(bit);
+        }
+        let (bytes, bit_len) = writer.finish();
+        assert_eq!(bytes, vec![0b1011_0001, 0b1010_0000]);
+
+        let reader = BitReader::with_bit_len(&bytes, bit_len);
+        assert_eq!(reader.size_hint(), (11, Some(11)));
+        assert_eq!(reader.collect::<Vec<_>>(), bits);
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_bits(4), Some(0b1011));
+        assert_eq!(reader.read_bits(8), Some(0b0001_1010));
+        assert_eq!(reader.position(), 12);
+        assert_eq!(reader.read_bits(5), None);
+        assert_eq!(reader.read_bits(4), Some(0));
+        assert_eq!(reader.read_bit(), None);
+    }
+
+    #[test]
+    fn test_bit_order() {
+        let mut writer = BitWriter::new().with_bit_order(BitOrder::LsbFirst);
+        writer.write_bit(true);
+        writer.write_bits(0b011, 3);
+        writer.write_bits(0b1_0101_0101, 9);
+        // The same bits as in `test_bit_writer`, each byte filled from its
+        // lowest bit.
+        assert_eq!(writer.finish(), (vec![0b0101_1101, 0b0001_0101], 13));
+
+        let bytes = [0b0101_1101, 0b0001_0101];
+        let mut reader = BitReader::with_bit_len(&bytes, 13).with_bit_order(BitOrder::LsbFirst);
+        assert_eq!(reader.read_bit(), Some(true));
+        assert_eq!(reader.read_bits(3), Some(0b011));
+        assert_eq!(reader.read_bits(9), Some(0b1_0101_0101));
+        assert_eq!(reader.read_bit(), None);
+    }
+}
@@ -0,0 +1,240 @@
+// Code tables computed by the compiler, for alphabets known ahead of time, so
+// encoding starts without building a tree.
+
+use alloc::vec::Vec;
+
+use crate::{bitio::BitWriter, EncodeError, HuffmanCode};
+
+// Nodes of a tree over at most 256 characters.
+const MAX_NODES: usize = 2 * 256 - 1;
+
+/// Packed Huffman codes for bytes, built in a `const` context by
+/// [`ConstCodeTable::new`] or the [`huffman_table!`](crate::huffman_table)
+/// macro. The codes are the ones [`huffman_codes`](crate::huffman_codes)
+/// gives for the same frequencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstCodeTable {
+    // Code bits and length of each byte; a length of 0 means no code.
+    codes: [(u32, u8); 256],
+}
+
+impl ConstCodeTable {
+    /// Builds the codes for `frequencies`, merging nodes in the same order as
+    /// [`huffman_tree`](crate::huffman_tree). Panics, which fails the build
+    /// when evaluated as a constant, if `frequencies` is empty, repeats a
+    /// byte, overflows `usize` or needs a code longer than 32 bits.
+    pub const fn new(frequencies: &[(u8, usize)]) -> Self {
+        assert!(
+            !frequencies.is_empty(),
+            "Cannot build a tree without characters"
+        );
+        let n = frequencies.len();
+        let mut value = [0; MAX_NODES];
+        let mut min_character = [0; MAX_NODES];
+        let mut parent = [0; MAX_NODES];
+        let mut is_right = [false; MAX_NODES];
+        let mut alive = [false; MAX_NODES];
+
+        let mut i = 0;
+        while i < n {
+            let (c, frequency) = frequencies[i];
+            let mut j = 0;
+            while j < i {
+                assert!(frequencies[j].0 != c, "Duplicate character");
+                j += 1;
+            }
+            value[i] = frequency;
+            min_character[i] = c;
+            alive[i] = true;
+            i += 1;
+        }
+
+        // Merge the two smallest nodes, by value and then by smallest
+        // character, until one is left.
+        let mut count = n;
+        while count < 2 * n - 1 {
+            let first = smallest(&value, &min_character, &alive, count);
+            alive[first] = false;
+            let second = smallest(&value, &min_character, &alive, count);
+            alive[second] = false;
+            let Some(sum) = value[first].checked_add(value[second]) else {
+                panic!("Frequencies overflow usize");
+            };
+            // The larger node goes on the left, the `0` edge.
+            let right = if value[second] > value[first] {
+                first
+            } else {
+                second
+            };
+            parent[first] = count;
+            parent[second] = count;
+            is_right[right] = true;
+            value[count] = sum;
+            min_character[count] = if min_character[first] < min_character[second] {
+                min_character[first]
+            } else {
+                min_character[second]
+            };
+            alive[count] = true;
+            count += 1;
+        }
+
+        let mut codes = [(0, 0); 256];
+        if n == 1 {
+            codes[frequencies[0].0 as usize] = (1, 1);
+            return ConstCodeTable { codes };
+        }
+        let root = 2 * n - 2;
+        let mut leaf = 0;
+        while leaf < n {
+            let (mut code, mut len) = (0u32, 0u8);
+            let mut node = leaf;
+            while node != root {
+                assert!(len < 32, "Codes must fit in 32 bits");
+                code |= (is_right[node] as u32) << len;
+                len += 1;
+                node = parent[node];
+            }
+            codes[frequencies[leaf].0 as usize] = (code, len);
+            leaf += 1;
+        }
+        ConstCodeTable { codes }
+    }
+
+    /// Code of `byte` as its bits, most significant first, and its length.
+    pub const fn code(&self, byte: u8) -> Option<(u32, u8)> {
+        match self.codes[byte as usize] {
+            (_, 0) => None,
+            code => Some(code),
+        }
+    }
+
+    /// Encodes `data`, returning the packed bytes and the number of
+    /// meaningful bits, as [`huffman_encode_with_codes`](crate::huffman_encode_with_codes)
+    /// does with [`codes`](Self::codes).
+    pub fn encode(&self, data: &[u8]) -> Result<(Vec<u8>, usize), EncodeError> {
+        let mut writer = BitWriter::with_capacity(data.len() / 2);
+        for &c in data {
+            let (code, len) = self.code(c).ok_or(EncodeError::NoCodeForSymbol(c))?;
+            writer.write_bits(code as u64, len);
+        }
+        Ok(writer.finish())
+    }
+
+    /// The codes as a [`HuffmanCode`], e.g. to build a decode tree with
+    /// [`build_decode_tree`](crate::build_decode_tree).
+    pub fn codes(&self) -> HuffmanCode {
+        (0..=255)
+            .filter_map(|c| {
+                let (code, len) = self.code(c)?;
+                let bits = (0..len)
+                    .rev()
+                    .map(|i| if code >> i & 1 == 1 { b'1' } else { b'0' })
+                    .collect();
+                Some((c, bits))
+            })
+            .collect()
+    }
+}
+
+// Index of the smallest live node among the first `count`, the one
+// `huffman_tree`'s heap would pop next.
+const fn smallest(
+    value: &[usize; MAX_NODES],
+    min_character: &[u8; MAX_NODES],
+    alive: &[bool; MAX_NODES],
+    count: usize,
+) -> usize {
+    let mut best = MAX_NODES;
+    let mut i = 0;
+    while i < count {
+        if alive[i]
+            && (best == MAX_NODES
+                || value[i] < value[best]
+                || (value[i] == value[best] && min_character[i] < min_character[best]))
+        {
+            best = i;
+        }
+        i += 1;
+    }
+    best
+}
+
+/// Builds a [`ConstCodeTable`] at compile time from `(byte, frequency)`
+/// pairs.
+///
+/// ```
+/// use huffman_coding::{huffman_table, ConstCodeTable};
+///
+/// static TABLE: ConstCodeTable = huffman_table![(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
+///
+/// let (encoded, bit_len) = TABLE.encode(b"AABCBAD").unwrap();
+/// assert_eq!((encoded, bit_len), (vec![0b1100_0100, 0b0101_1000], 13));
+/// ```
+#[macro_export]
+macro_rules! huffman_table {
+    ($(($c:expr, $frequency:expr)),* $(,)?) => {{
+        const TABLE: $crate::ConstCodeTable =
+            $crate::ConstCodeTable::new(&[$(($c, $frequency)),*]);
+        TABLE
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{huffman_codes, huffman_encode_with_codes, huffman_tree};
+
+    #[test]
+    fn test_huffman_table_matches_runtime() {
+        const SKEWED: [(u8, usize); 8] = [
+            (b'A', 40),
+            (b'B', 20),
+            (b'C', 12),
+            (b'D', 10),
+            (b'E', 8),
+            (b'F', 5),
+            (b'G', 3),
+            (b'H', 2),
+        ];
+        const SKEWED_TABLE: ConstCodeTable = ConstCodeTable::new(&SKEWED);
+        assert_eq!(SKEWED_TABLE.codes(), huffman_codes(&huffman_tree(&SKEWED)));
+
+        // Ties are broken as the heap does, by smallest character.
+        let table = huffman_table![(b'x', 5), (b'a', 5), (b'm', 5), (b'c', 5), (b'z', 1)];
+        let frequencies = [(b'x', 5), (b'a', 5), (b'm', 5), (b'c', 5), (b'z', 1)];
+        assert_eq!(table.codes(), huffman_codes(&huffman_tree(&frequencies)));
+
+        const ALL: ConstCodeTable = ConstCodeTable::new(&{
+            let mut frequencies = [(0, 0); 256];
+            let mut c = 0;
+            while c < 256 {
+                frequencies[c] = (c as u8, 1 + c % 7);
+                c += 1;
+            }
+            frequencies
+        });
+        let frequencies = (0..=255)
+            .map(|c| (c, 1 + c as usize % 7))
+            .collect::<Vec<_>>();
+        assert_eq!(ALL.codes(), huffman_codes(&huffman_tree(&frequencies)));
+
+        let single = huffman_table![(b'A', 3)];
+        assert_eq!(single.codes(), huffman_codes(&huffman_tree(&[(b'A', 3)])));
+    }
+
+    #[test]
+    fn test_const_table_encode() {
+        let table = huffman_table![(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
+        assert_eq!(table.code(b'A'), Some((0b1, 1)));
+        assert_eq!(table.code(b'Z'), None);
+        assert_eq!(
+            table.encode(b"AABCBAD").unwrap(),
+            huffman_encode_with_codes(b"AABCBAD", &table.codes()).unwrap()
+        );
+        assert!(matches!(
+            table.encode(b"AZ"),
+            Err(EncodeError::NoCodeForSymbol(b'Z'))
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Map;
+
+/// Serializable form of the decode map returned by `huffman_encode`.
+///
+/// Formats such as JSON only allow string keys, so each code is stored as
+/// its `"0"`/`"1"` text, sorted, mapping to its character.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DecodeTable<S = u8>(pub Map<Vec<u8>, S>);
+
+impl<S> From<Map<Vec<u8>, S>> for DecodeTable<S> {
+    fn from(codes: Map<Vec<u8>, S>) -> Self {
+        DecodeTable(codes)
+    }
+}
+
+impl<S> From<DecodeTable<S>> for Map<Vec<u8>, S> {
+    fn from(table: DecodeTable<S>) -> Self {
+        table.0
+    }
+}
+
+impl<S: Serialize> Serialize for DecodeTable<S> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        self.0
+            .iter()
+            .map(|(code, c)| (String::from_utf8_lossy(code), c))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+}
+
+impl<'de, S: Deserialize<'de>> Deserialize<'de> for DecodeTable<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BTreeMap::<String, S>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(code, c)| {
+                if code.is_empty() || code.bytes().any(|b| b != b'0' && b != b'1') {
+                    return Err(D::Error::custom(format!("invalid code {code:?}")));
+                }
+                Ok((code.into_bytes(), c))
+            })
+            .collect::<Result<_, _>>()
+            .map(DecodeTable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{huffman_decode, huffman_encode};
+
+    #[test]
+    fn test_decode_table_round_trip() {
+        let (encoded, codes, _) = huffman_encode(&"AABCBAD".to_string());
+        let json = serde_json::to_string(&DecodeTable::from(codes.clone())).unwrap();
+        assert_eq!(json, r#"{"00":66,"010":67,"011":68,"1":65}"#);
+
+        let table = serde_json::from_str::<DecodeTable>(&json).unwrap();
+        assert_eq!(table.0, codes);
+        assert_eq!(
+            huffman_decode(&encoded, &table.into(), 7).unwrap(),
+            "AABCBAD"
+        );
+    }
+
+    #[test]
+    fn test_decode_table_rejects_invalid_codes() {
+        for json in [r#"{"":65}"#, r#"{"012":65}"#] {
+            assert!(serde_json::from_str::<DecodeTable>(json).is_err());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+};
+
+use crate::HuffmanTreeNode;
+
+/// Renders the tree as a Graphviz digraph, e.g. for `dot -Tpng`. Internal
+/// nodes are labeled with their value, leaves with `character: frequency`
+/// and edges with their bit. Bytes outside printable ASCII are shown as
+/// `\xNN`.
+pub fn to_dot(tree: &HuffmanTreeNode) -> String {
+    let mut dot = String::from("digraph huffman {\n");
+    let mut next_id = 1;
+    let mut stack = vec![(tree, 0)];
+    while let Some((node, id)) = stack.pop() {
+        match node {
+            HuffmanTreeNode::Character(node) => {
+                let label = dot_label(node.character);
+                dot += &format!(
+                    "    n{id} [label=\"{label}: {}\", shape=box];\n",
+                    node.frequency
+                );
+            }
+            HuffmanTreeNode::Value(node) => {
+                dot += &format!("    n{id} [label=\"{}\"];\n", node.value);
+                for (child, bit) in [(&node.left, 0), (&node.right, 1)] {
+                    if let Some(child) = child {
+                        dot += &format!("    n{id} -> n{next_id} [label=\"{bit}\"];\n");
+                        stack.push((child, next_id));
+                        next_id += 1;
+                    }
+                }
+            }
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+fn dot_label(character: u8) -> String {
+    match character {
+        b'"' => "\\\"".to_string(),
+        b'\\' => "\\\\".to_string(),
+        0x20..=0x7e => (character as char).to_string(),
+        _ => format!("\\\\x{character:02x}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frequency_counter, frequency_list, huffman_tree};
+
+    #[test]
+    fn test_to_dot() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let dot = to_dot(&tree);
+        assert!(dot.starts_with("digraph huffman {\n"));
+        assert!(dot.ends_with("}\n"));
+        for line in [
+            "n0 [label=\"7\"];",
+            "n0 -> n1 [label=\"0\"];",
+            "n0 -> n2 [label=\"1\"];",
+            "n1 [label=\"4\"];",
+            "n2 [label=\"A: 3\", shape=box];",
+            "n3 [label=\"B: 2\", shape=box];",
+            "n4 [label=\"2\"];",
+            "n1 -> n3 [label=\"0\"];",
+            "n1 -> n4 [label=\"1\"];",
+            "n5 [label=\"C: 1\", shape=box];",
+            "n6 [label=\"D: 1\", shape=box];",
+        ] {
+            assert!(dot.contains(line), "missing {line} in\n{dot}");
+        }
+        assert_eq!(dot.matches("->").count(), 6);
+    }
+
+    #[test]
+    fn test_to_dot_escapes_labels() {
+        let tree = huffman_tree(&[(b'\n', 2), (b'"', 1), (0xff, 1)]);
+        let dot = to_dot(&tree);
+        assert!(dot.contains("[label=\"\\\\x0a: 2\", shape=box]"));
+        assert!(dot.contains("[label=\"\\\": 1\", shape=box]"));
+        assert!(dot.contains("[label=\"\\\\xff: 1\", shape=box]"));
+    }
+}
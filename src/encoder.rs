@@ -0,0 +1,204 @@
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::{
+    bitio::BitOrder,
+    decompress, decompress_blocks,
+    format::{compress_blocks_with, compress_with_options},
+    DecodeError,
+};
+
+/// Compressor with configurable options, created with
+/// [`HuffmanEncoder::builder`]. The defaults produce the same output as
+/// [`compress`](crate::compress).
+///
+/// ```
+/// use huffman_coding::HuffmanEncoder;
+///
+/// let encoder = HuffmanEncoder::builder()
+///     .max_code_length(15)
+///     .with_checksum(true)
+///     .block_size(64 * 1024)
+///     .build();
+/// let blob = encoder.encode(b"AABCBAD");
+/// assert_eq!(encoder.decode(&blob).unwrap(), b"AABCBAD");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HuffmanEncoder {
+    max_code_length: Option<u8>,
+    checksum: bool,
+    block_size: Option<usize>,
+    bit_order: BitOrder,
+}
+
+impl HuffmanEncoder {
+    pub fn builder() -> HuffmanEncoderBuilder {
+        HuffmanEncoderBuilder::default()
+    }
+
+    /// Compresses `data` in the [`compress`](crate::compress) format, or the
+    /// [`compress_blocks`](crate::compress_blocks) one when a block size is
+    /// set.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let compress = |data: &[u8]| {
+            compress_with_options(data, self.checksum, self.max_code_length, self.bit_order)
+        };
+        match self.block_size {
+            Some(block_size) => compress_blocks_with(data, block_size, compress),
+            None => compress(data),
+        }
+    }
+
+    /// Restores data written by [`encode`](Self::encode) with the same block
+    /// size setting.
+    pub fn decode(&self, blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        match self.block_size {
+            Some(_) => decompress_blocks(blob),
+            None => decompress(blob).map(Cow::into_owned),
+        }
+    }
+}
+
+impl Default for HuffmanEncoder {
+    fn default() -> Self {
+        HuffmanEncoder::builder().build()
+    }
+}
+
+/// Builder for [`HuffmanEncoder`]. By default codes are unlimited, a
+/// checksum is stored, the data is compressed as a single block and bits are
+/// packed MSB-first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HuffmanEncoderBuilder {
+    max_code_length: Option<u8>,
+    checksum: bool,
+    block_size: Option<usize>,
+    bit_order: BitOrder,
+}
+
+impl Default for HuffmanEncoderBuilder {
+    fn default() -> Self {
+        HuffmanEncoderBuilder {
+            max_code_length: None,
+            checksum: true,
+            block_size: None,
+            bit_order: BitOrder::MsbFirst,
+        }
+    }
+}
+
+impl HuffmanEncoderBuilder {
+    /// Limits codes to `max_len` bits. Panics if `max_len` is below 8, since
+    /// all 256 bytes must fit.
+    pub fn max_code_length(mut self, max_len: u8) -> Self {
+        assert!(max_len >= 8, "Code length limit must fit 256 characters");
+        self.max_code_length = Some(max_len);
+        self
+    }
+
+    /// Whether to store a CRC-32 of the data to verify when decoding.
+    pub fn with_checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Compresses the data in independent blocks of `block_size` bytes.
+    /// Panics if `block_size` is 0.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        assert!(block_size > 0, "Block size must be positive");
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Packs the bitstream in `order`. The choice is stored in the header, so
+    /// decoding needs no setting.
+    pub fn bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    pub fn build(self) -> HuffmanEncoder {
+        HuffmanEncoder {
+            max_code_length: self.max_code_length,
+            checksum: self.checksum,
+            block_size: self.block_size,
+            bit_order: self.bit_order,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, compress_blocks};
+
+    const DATA: &[u8] = b"this is an example of a huffman tree";
+
+    #[test]
+    fn test_encoder_defaults_match_compress() {
+        let encoder = HuffmanEncoder::builder().build();
+        assert_eq!(encoder, HuffmanEncoder::default());
+        assert_eq!(encoder.encode(DATA), compress(DATA));
+        assert_eq!(encoder.encode(b""), compress(b""));
+        assert_eq!(encoder.decode(&encoder.encode(DATA)).unwrap(), DATA);
+    }
+
+    #[test]
+    fn test_encoder_options_round_trip() {
+        let encoders = [
+            HuffmanEncoder::builder().max_code_length(8).build(),
+            HuffmanEncoder::builder().with_checksum(false).build(),
+            HuffmanEncoder::builder().block_size(7).build(),
+            HuffmanEncoder::builder()
+                .max_code_length(15)
+                .with_checksum(false)
+                .block_size(64 * 1024)
+                .build(),
+        ];
+        for encoder in encoders {
+            assert_eq!(encoder.decode(&encoder.encode(DATA)).unwrap(), DATA);
+        }
+    }
+
+    #[test]
+    fn test_encoder_block_size() {
+        let encoder = HuffmanEncoder::builder().block_size(7).build();
+        assert_eq!(encoder.encode(DATA), compress_blocks(DATA, 7));
+        let unchecked = HuffmanEncoder::builder()
+            .with_checksum(false)
+            .block_size(7)
+            .build();
+        // Every block saves the 4 byte checksum.
+        assert_eq!(
+            unchecked.encode(DATA).len(),
+            compress_blocks(DATA, 7).len() - 4 * DATA.len().div_ceil(7)
+        );
+    }
+
+    #[test]
+    fn test_encoder_bit_order() {
+        let msb = HuffmanEncoder::builder().build();
+        let lsb = HuffmanEncoder::builder()
+            .bit_order(BitOrder::LsbFirst)
+            .build();
+        let (msb_blob, lsb_blob) = (msb.encode(DATA), lsb.encode(DATA));
+        assert_eq!(msb.decode(&msb_blob).unwrap(), DATA);
+        assert_eq!(lsb.decode(&lsb_blob).unwrap(), DATA);
+        // Either encoder reads both, the order being in the header.
+        assert_eq!(msb.decode(&lsb_blob).unwrap(), DATA);
+
+        // Same length and table, each bitstream byte reversed.
+        assert_eq!(msb_blob.len(), lsb_blob.len());
+        let table_end = 19 + u16::from_le_bytes([msb_blob[17], msb_blob[18]]) as usize;
+        assert_eq!(msb_blob[5..table_end], lsb_blob[5..table_end]);
+        assert_ne!(msb_blob[table_end..], lsb_blob[table_end..]);
+        for (msb_byte, lsb_byte) in msb_blob[table_end..].iter().zip(&lsb_blob[table_end..]) {
+            assert_eq!(msb_byte.reverse_bits(), *lsb_byte);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Code length limit must fit 256 characters")]
+    fn test_encoder_max_code_length_too_small() {
+        HuffmanEncoder::builder().max_code_length(7);
+    }
+}
@@ -0,0 +1,118 @@
+use core::{error::Error, fmt};
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The data ended before every character was decoded.
+    UnexpectedEof,
+    /// The bits do not match any code in the table.
+    InvalidCode,
+    /// The magic bytes or a header field are invalid.
+    BadHeader,
+    /// The serialized tree ended before it was complete.
+    TruncatedTree,
+    /// The decoded bytes are not valid UTF-8 text.
+    InvalidUtf8,
+    /// The decompressed data does not match the checksum in the header.
+    ChecksumMismatch,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => {
+                write!(f, "encoded data ended before all characters were decoded")
+            }
+            DecodeError::InvalidCode => write!(f, "encoded data contains an unknown code"),
+            DecodeError::BadHeader => write!(f, "compressed header is invalid"),
+            DecodeError::TruncatedTree => write!(f, "serialized tree is truncated"),
+            DecodeError::InvalidUtf8 => write!(f, "decoded text is not valid UTF-8"),
+            DecodeError::ChecksumMismatch => {
+                write!(f, "decompressed data does not match its checksum")
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The code table has no entry for this byte.
+    NoCodeForSymbol(u8),
+    /// The alphabet does not fit in codes of at most this many bits.
+    CodeLengthLimitTooSmall(u8),
+    /// The frequencies add up to more than `usize::MAX`.
+    FrequencyOverflow,
+    /// The output buffer cannot hold the encoded data.
+    BufferTooSmall,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::NoCodeForSymbol(c) => write!(f, "no code for byte {c:#04x}"),
+            EncodeError::CodeLengthLimitTooSmall(max_len) => {
+                write!(f, "alphabet does not fit in codes of {max_len} bits")
+            }
+            EncodeError::FrequencyOverflow => write!(f, "character frequencies overflow usize"),
+            EncodeError::BufferTooSmall => write!(f, "output buffer is too small"),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// The code of the first byte is a prefix of, or equal to, the code of the
+    /// second, so decoding would be ambiguous.
+    NotPrefixFree(u8, u8),
+    /// The byte has an empty code.
+    EmptyCode(u8),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NotPrefixFree(prefix, c) => write!(
+                f,
+                "code of byte {prefix:#04x} is a prefix of the code of byte {c:#04x}"
+            ),
+            ValidationError::EmptyCode(c) => write!(f, "byte {c:#04x} has an empty code"),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{boxed::Box, string::ToString};
+
+    #[test]
+    fn test_decode_error_display() {
+        assert_eq!(
+            DecodeError::BadHeader.to_string(),
+            "compressed header is invalid"
+        );
+        let error: Box<dyn Error> = Box::new(DecodeError::InvalidCode);
+        assert_eq!(error.to_string(), "encoded data contains an unknown code");
+    }
+
+    #[test]
+    fn test_encode_error_display() {
+        assert_eq!(
+            EncodeError::NoCodeForSymbol(b'A').to_string(),
+            "no code for byte 0x41"
+        );
+    }
+
+    #[test]
+    fn test_validation_error_display() {
+        assert_eq!(
+            ValidationError::NotPrefixFree(b'A', b'B').to_string(),
+            "code of byte 0x41 is a prefix of the code of byte 0x42"
+        );
+    }
+}
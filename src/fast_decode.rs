@@ -0,0 +1,159 @@
+// Table-driven decoding: the next `bits` bits of the stream index a table
+// giving the character they start with and its code length, so most codes
+// are decoded with one lookup instead of one tree edge per bit.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{build_decode_tree, decode_step, DecodeError, HuffmanCode, HuffmanTreeNode};
+
+/// Lookup table built by [`build_fast_decode_table`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastDecodeTable {
+    bits: u8,
+    // Character and code length for each `bits`-bit prefix. A length of 0
+    // marks a prefix that starts a longer code or none at all.
+    entries: Vec<(u8, u8)>,
+    // Decodes what the table cannot resolve.
+    tree: HuffmanTreeNode,
+}
+
+/// Builds a table indexed by the next `bits` bits of a stream encoded with
+/// `codes`. It holds `2^bits` entries; codes longer than `bits` fall back to
+/// walking the tree. Panics if `bits` is not between 1 and 16.
+pub fn build_fast_decode_table(codes: &HuffmanCode, bits: u8) -> FastDecodeTable {
+    assert!((1..=16).contains(&bits), "Table index must be 1 to 16 bits");
+    let mut entries = vec![(0, 0); 1 << bits];
+    for (&c, code) in codes {
+        if code.len() > bits as usize {
+            continue;
+        }
+        let prefix = code
+            .iter()
+            .fold(0, |prefix, &bit| prefix << 1 | (bit == b'1') as usize);
+        // Every index starting with the code decodes to it, whatever follows.
+        let free_bits = bits as usize - code.len();
+        let start = prefix << free_bits;
+        entries[start..start + (1 << free_bits)].fill((c, code.len() as u8));
+    }
+    FastDecodeTable {
+        bits,
+        entries,
+        tree: build_decode_tree(codes),
+    }
+}
+
+impl FastDecodeTable {
+    /// Decodes `count` bytes from the packed bitstream `encoded`, giving the
+    /// same result as [`decode_bits`](crate::decode_bits) with the tree of
+    /// the same codes.
+    pub fn decode(&self, encoded: &[u8], count: usize) -> Result<Vec<u8>, DecodeError> {
+        let bit_len = encoded.len() * 8;
+        let bits = self.bits as usize;
+        // Each character takes at least one bit, which bounds a corrupt count.
+        let mut decoded = Vec::with_capacity(count.min(bit_len));
+        let mut position = 0;
+        while decoded.len() < count {
+            if position + bits <= bit_len {
+                let (c, len) = self.entries[peek(encoded, position, self.bits)];
+                if len > 0 {
+                    decoded.push(c);
+                    position += len as usize;
+                    continue;
+                }
+            }
+            decoded.push(self.walk(encoded, &mut position)?);
+        }
+        Ok(decoded)
+    }
+
+    // Decodes one character bit by bit from `position`, for long codes and
+    // the last bits of the stream.
+    fn walk(&self, encoded: &[u8], position: &mut usize) -> Result<u8, DecodeError> {
+        let mut node = &self.tree;
+        loop {
+            let byte = encoded
+                .get(*position / 8)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            let bit = byte >> (7 - *position % 8) & 1 == 1;
+            *position += 1;
+            if let Some(c) = decode_step(&self.tree, &mut node, bit)? {
+                return Ok(c);
+            }
+        }
+    }
+}
+
+// The `bits` bits of `encoded` starting at bit `position`, which the caller
+// keeps within the data.
+#[inline]
+fn peek(encoded: &[u8], position: usize, bits: u8) -> usize {
+    // Reaching 16 bits past an unaligned position spans up to 3 bytes.
+    let window = (0..3).fold(0u32, |window, i| {
+        window << 8 | *encoded.get(position / 8 + i).unwrap_or(&0) as u32
+    });
+    ((window << (8 + position % 8)) >> (32 - bits)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{bitio::BitReader, decode_bits, huffman_build, huffman_encode_with_codes};
+
+    #[test]
+    fn test_fast_decode_matches_tree() {
+        let mut r = StdRng::seed_from_u64(42);
+        let skewed = (0..4096)
+            .map(|_| (r.gen::<f64>().powi(4) * 200.0) as u8)
+            .collect::<Vec<u8>>();
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b"AABCBAD"[..], b"AAAA", &skewed, &random] {
+            let model = huffman_build(data);
+            let (encoded, _) = model.encode(data).unwrap();
+            let expected = decode_bits(BitReader::new(&encoded), model.tree(), data.len()).unwrap();
+            assert_eq!(expected, data);
+            // Tables smaller and larger than the longest code.
+            for bits in [1, 4, 8, 11, 16] {
+                let table = build_fast_decode_table(model.codes(), bits);
+                assert_eq!(table.decode(&encoded, data.len()).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_decode_errors() {
+        // A=1, B=00, C=010, D=011.
+        let model = huffman_build(b"AABCBAD");
+        let (encoded, _) = model.encode(b"AABCBAD").unwrap();
+        let table = build_fast_decode_table(model.codes(), 8);
+        assert_eq!(table.decode(&encoded, 0).unwrap(), b"");
+        assert!(matches!(
+            table.decode(&encoded, 20),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        // A count far beyond the stream fails instead of allocating it.
+        assert!(matches!(
+            table.decode(&encoded, usize::MAX),
+            Err(DecodeError::UnexpectedEof)
+        ));
+
+        // No code starts with 11.
+        let codes = [(b'A', b"0".to_vec()), (b'B', b"10".to_vec())]
+            .into_iter()
+            .collect();
+        let table = build_fast_decode_table(&codes, 4);
+        let (encoded, _) = huffman_encode_with_codes(b"AB", &codes).unwrap();
+        assert_eq!(table.decode(&encoded, 2).unwrap(), b"AB");
+        assert!(matches!(
+            table.decode(&[0b1100_0000], 1),
+            Err(DecodeError::InvalidCode)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Table index must be 1 to 16 bits")]
+    fn test_fast_decode_table_too_wide() {
+        build_fast_decode_table(&HuffmanCode::default(), 17);
+    }
+}
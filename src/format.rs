@@ -0,0 +1,1558 @@
+// Layout of a compressed blob, integers little-endian:
+//
+// | magic | flags: u8 | length: u64 | CRC-32: u32 | table length: u16 | table | bitstream |
+//
+// The CRC-32 of the original data is only present when `FLAG_CHECKSUM` is
+// set, which `compress` always does. `FLAG_MTF` and `FLAG_RLE` mean the
+// bitstream codes the data after `mtf_encode` and `rle_encode` respectively,
+// in that order when both are set, and the length is that of the transformed
+// data. `FLAG_STORED` replaces the table and bitstream with the bytes
+// themselves, which `compress` does when coding would not make them smaller.
+// `FLAG_LSB_FIRST` means the bitstream is packed with `BitOrder::LsbFirst`.
+// `FLAG_SINGLE` replaces the table and bitstream with the one byte the data
+// repeats, which `compress` does whenever there is only one, so a run of any
+// length takes a fixed number of bytes.
+// Other flag bits must be clear. With the "HUFF" magic the table is the tree
+// written by `serialize_tree`; with "HUFC" it holds the code lengths written
+// by `serialize_code_lengths` and the codes are canonical. With "HUFX" the
+// table is a tree with 9-bit leaves whose character 256 is an escape, see
+// `compress_with_escape`. The bitstream fills bytes most significant bit
+// first, zero-padding the last one, unless `FLAG_LSB_FIRST` is set. Empty
+// input stops right after the checksum.
+//
+// `compress_with_eof` uses a layout of its own, described on the function.
+
+use alloc::{borrow::Cow, vec::Vec};
+#[cfg(feature = "std")]
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    bitio::{BitOrder, BitReader, BitWriter},
+    build_decode_tree, byte_frequency_list, canonical_codes_from_lengths,
+    checksum::crc32,
+    decode_bits, decode_characters, decode_step, decode_tree_for, deserialize_code_lengths,
+    deserialize_tree, encode_packed, frequency_list, huffman_codes, huffman_tree,
+    length_limited_codes, serialize_code_lengths, serialize_tree,
+    transform::{mtf_decode, mtf_encode, rle_decode, rle_encode},
+    unpack_bits, write_byte_codes, DecodeError, HuffmanCode, Map,
+};
+#[cfg(feature = "std")]
+use crate::{checksum::Crc32, FrequencyModel, HuffmanWriter};
+
+const MAGIC: &[u8; 4] = b"HUFF";
+const CANONICAL_MAGIC: &[u8; 4] = b"HUFC";
+const EOF_MAGIC: &[u8; 4] = b"HUFE";
+const ESCAPE_MAGIC: &[u8; 4] = b"HUFX";
+
+// End-of-data character of `compress_with_eof`, one past the last byte.
+const EOF: u16 = 256;
+
+// Character of `compress_with_escape` standing for any rare byte, which
+// follows it as 8 literal bits.
+const ESCAPE: u16 = 256;
+
+const FLAG_CHECKSUM: u8 = 1;
+const FLAG_RLE: u8 = 2;
+const FLAG_MTF: u8 = 4;
+const FLAG_STORED: u8 = 8;
+const FLAG_LSB_FIRST: u8 = 16;
+const FLAG_SINGLE: u8 = 32;
+
+// Longest code `compress_canonical` produces, so each length fits in 4 bits.
+const MAX_CANONICAL_CODE_LEN: u8 = 15;
+
+/// Compresses `data` into a blob that [`decompress`] restores. Data made of
+/// one repeated byte is recorded as that byte and the length alone, with no
+/// table or bitstream, so it takes 18 bytes however long it is.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    compress_with_options(data, true, None, BitOrder::MsbFirst)
+}
+
+/// [`compress`] with the checksum optional, the bitstream packed in
+/// `bit_order` and, when `max_code_length` is set, codes limited to that many
+/// bits. The limit must be at least 8 so any set of bytes fits.
+pub(crate) fn compress_with_options(
+    data: &[u8],
+    checksum: bool,
+    max_code_length: Option<u8>,
+    bit_order: BitOrder,
+) -> Vec<u8> {
+    let mut flags = if checksum { FLAG_CHECKSUM } else { 0 };
+    if bit_order == BitOrder::LsbFirst {
+        flags |= FLAG_LSB_FIRST;
+    }
+    compress_with_flags(data, flags, max_code_length)
+}
+
+/// Like [`compress`], but runs of 4 or more equal bytes are shortened to the
+/// byte and a repeat count before coding, which pays off on data with long
+/// runs. [`decompress`] reads the result.
+pub fn compress_rle(data: &[u8]) -> Vec<u8> {
+    compress_with_flags(data, FLAG_CHECKSUM | FLAG_RLE, None)
+}
+
+/// Like [`compress`], but the data goes through [`mtf_encode`] first, which
+/// helps when the same few bytes keep recurring close together.
+/// [`decompress`] reads the result.
+pub fn compress_mtf(data: &[u8]) -> Vec<u8> {
+    compress_with_flags(data, FLAG_CHECKSUM | FLAG_MTF, None)
+}
+
+fn compress_with_flags(data: &[u8], flags: u8, max_code_length: Option<u8>) -> Vec<u8> {
+    let mut transformed = None;
+    if flags & FLAG_MTF != 0 {
+        transformed = Some(mtf_encode(data));
+    }
+    if flags & FLAG_RLE != 0 {
+        transformed = Some(rle_encode(transformed.as_deref().unwrap_or(data)));
+    }
+    let symbols = transformed.as_deref().unwrap_or(data);
+    if symbols.is_empty() {
+        return write_header(MAGIC, flags, 0, data);
+    }
+
+    let frequency_list = byte_frequency_list(symbols);
+    if let [(c, _)] = frequency_list[..] {
+        let mut blob = write_header(MAGIC, flags | FLAG_SINGLE, symbols.len(), data);
+        blob.push(c);
+        return blob;
+    }
+    let mut tree = huffman_tree(&frequency_list);
+    if let Some(max_len) = max_code_length.filter(|&max_len| tree.depth() > max_len as usize) {
+        let Ok(codes) = length_limited_codes(&frequency_list.iter().copied().collect(), max_len)
+        else {
+            unreachable!("Code length limit must fit 256 characters");
+        };
+        // Optimal codes are complete, so every node of this tree is full.
+        tree = build_decode_tree(&codes);
+    }
+    let table = serialize_tree(&tree, 8);
+    let codes = huffman_codes(&tree);
+    if should_store(&frequency_list, &codes, table.len()) {
+        let mut blob = write_header(MAGIC, flags | FLAG_STORED, symbols.len(), data);
+        blob.extend(symbols);
+        return blob;
+    }
+
+    let mut blob = write_header(MAGIC, flags, symbols.len(), data);
+    write_table_and_bitstream(&mut blob, &table, symbols, &codes, bit_order(flags));
+    blob
+}
+
+/// Length of the blob [`compress`] writes for `data`, found from the code
+/// lengths without writing the bitstream. It counts the header and table, so
+/// it can exceed `data.len()` for short inputs, where coding does not pay.
+pub fn total_compressed_size(data: &[u8]) -> usize {
+    // Magic, flags, length and checksum.
+    const HEADER_LEN: usize = 17;
+    let frequency_list = byte_frequency_list(data);
+    match frequency_list[..] {
+        [] => return HEADER_LEN,
+        [_] => return HEADER_LEN + 1,
+        _ => {}
+    }
+    let tree = huffman_tree(&frequency_list);
+    let table_len = serialize_tree(&tree, 8).len();
+    let codes = huffman_codes(&tree);
+    if should_store(&frequency_list, &codes, table_len) {
+        return HEADER_LEN + data.len();
+    }
+    let bit_len = frequency_list
+        .iter()
+        .map(|(c, count)| count * codes[c].len())
+        .sum::<usize>();
+    HEADER_LEN + 2 + table_len + bit_len.div_ceil(8)
+}
+
+// Whether coding `frequency_list` with `codes` is not worth it: the codes
+// average close to 8 bits, as for uniformly spread bytes, and together with
+// the table they take no less room than the bytes themselves.
+fn should_store(frequency_list: &[(u8, usize)], codes: &HuffmanCode, table_len: usize) -> bool {
+    let len = frequency_list.iter().map(|(_, count)| count).sum::<usize>();
+    let bit_len = frequency_list
+        .iter()
+        .map(|(c, count)| count * codes[c].len())
+        .sum::<usize>();
+    bit_len >= len * 15 / 2 && 2 + table_len + bit_len.div_ceil(8) >= len
+}
+
+/// Restores data written by [`compress`] and its variants sharing the "HUFF"
+/// header, such as [`compress_rle`] and [`compress_mtf`]. Data that was
+/// stored rather than coded is borrowed from `blob` instead of copied.
+pub fn decompress(blob: &[u8]) -> Result<Cow<'_, [u8]>, DecodeError> {
+    let known_flags =
+        FLAG_CHECKSUM | FLAG_RLE | FLAG_MTF | FLAG_STORED | FLAG_LSB_FIRST | FLAG_SINGLE;
+    let (header, blob) = read_header(blob, MAGIC, known_flags)?;
+    let mut data = if header.flags & FLAG_STORED != 0 {
+        Cow::Borrowed(blob.get(..header.len).ok_or(DecodeError::UnexpectedEof)?)
+    } else if header.flags & FLAG_SINGLE != 0 {
+        let &c = blob.first().ok_or(DecodeError::UnexpectedEof)?;
+        Cow::Owned(repeat_byte(c, header.len)?)
+    } else if header.len == 0 {
+        Cow::Borrowed(&[][..])
+    } else {
+        let (serialized_tree, bitstream) = split_table(blob)?;
+        let tree = deserialize_tree(serialized_tree, 8)?;
+        let bits = BitReader::new(bitstream).with_bit_order(bit_order(header.flags));
+        Cow::Owned(decode_bits(bits, &tree, header.len)?)
+    };
+    if header.flags & FLAG_RLE != 0 {
+        data = Cow::Owned(rle_decode(&data)?);
+    }
+    if header.flags & FLAG_MTF != 0 {
+        data = Cow::Owned(mtf_decode(&data));
+    }
+    verify_checksum(data, header.checksum)
+}
+
+/// Like [`compress`], but with canonical codes of at most 15 bits, so only
+/// their lengths are stored instead of the tree. The header is smaller
+/// whenever the alphabet is more than a handful of bytes, at the cost of
+/// slightly longer codes if the limit kicks in.
+pub fn compress_canonical(data: &[u8]) -> Vec<u8> {
+    let mut blob = write_header(CANONICAL_MAGIC, FLAG_CHECKSUM, data.len(), data);
+    if data.is_empty() {
+        return blob;
+    }
+
+    let counter = byte_frequency_list(data).into_iter().collect();
+    let Ok(codes) = length_limited_codes(&counter, MAX_CANONICAL_CODE_LEN) else {
+        unreachable!("256 characters always fit in 15 bit codes");
+    };
+    let lengths = codes
+        .iter()
+        .map(|(&c, code)| (c, code.len() as u8))
+        .collect();
+    write_table_and_bitstream(
+        &mut blob,
+        &serialize_code_lengths(&lengths),
+        data,
+        &codes,
+        BitOrder::MsbFirst,
+    );
+    blob
+}
+
+/// Restores data written by [`compress_canonical`].
+pub fn decompress_canonical(blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (header, blob) = read_header(blob, CANONICAL_MAGIC, FLAG_CHECKSUM)?;
+    if header.len == 0 {
+        return verify_checksum(Vec::new(), header.checksum);
+    }
+
+    let (lengths, bitstream) = split_table(blob)?;
+    let codes = canonical_codes_from_lengths(&deserialize_code_lengths(lengths)?);
+    let data = decode_characters(
+        unpack_bits(bitstream),
+        &build_decode_tree(&codes),
+        Some(header.len),
+    )?;
+    verify_checksum(data, header.checksum)
+}
+
+/// Alternative to [`compress`] that does not store the original length:
+/// the tree has an extra end-of-data character, 256, whose code ends the
+/// bitstream. The blob is `| magic "HUFE" | tree length: u16 | tree |
+/// bitstream |`, the tree having 9-bit leaves.
+pub fn compress_with_eof(data: &[u8]) -> Vec<u8> {
+    let mut counter = byte_frequency_list(data)
+        .into_iter()
+        .map(|(c, f)| (c as u16, f))
+        .collect::<Map<_, _>>();
+    counter.insert(EOF, 1);
+    let tree = huffman_tree(&frequency_list(&counter));
+    let symbols = data
+        .iter()
+        .map(|&c| c as u16)
+        .chain([EOF])
+        .collect::<Vec<_>>();
+
+    let mut blob = EOF_MAGIC.to_vec();
+    let serialized_tree = serialize_tree(&tree, 9);
+    blob.extend((serialized_tree.len() as u16).to_le_bytes());
+    blob.extend(serialized_tree);
+    let Ok((bitstream, _)) = encode_packed(&symbols, &huffman_codes(&tree)) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    blob.extend(bitstream);
+    blob
+}
+
+/// Restores data written by [`compress_with_eof`], stopping at the
+/// end-of-data code. Anything after it is ignored.
+pub fn decompress_with_eof(blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let blob = blob.strip_prefix(EOF_MAGIC).ok_or(DecodeError::BadHeader)?;
+    let (serialized_tree, bitstream) = split_table(blob)?;
+    let tree = deserialize_tree::<u16>(serialized_tree, 9)?;
+    let tree = decode_tree_for(&tree);
+
+    let mut data = Vec::new();
+    let mut node = &*tree;
+    for bit in BitReader::new(bitstream) {
+        match decode_step(&tree, &mut node, bit)? {
+            Some(EOF) => return Ok(data),
+            Some(c) => data.push(u8::try_from(c).map_err(|_| DecodeError::BadHeader)?),
+            None => {}
+        }
+    }
+    Err(DecodeError::UnexpectedEof)
+}
+
+/// Like [`compress`], but bytes occurring fewer than `threshold` times share
+/// a single escape code followed by the byte itself. A long tail of rare
+/// bytes then costs one leaf instead of one each, which shrinks the tree and
+/// shortens the codes of common bytes, for 8 more bits per rare occurrence.
+pub fn compress_with_escape(data: &[u8], threshold: usize) -> Vec<u8> {
+    let mut blob = write_header(ESCAPE_MAGIC, FLAG_CHECKSUM, data.len(), data);
+    if data.is_empty() {
+        return blob;
+    }
+
+    let mut rare = [false; 256];
+    let mut counter = Map::new();
+    for (c, f) in byte_frequency_list(data) {
+        if f < threshold {
+            rare[c as usize] = true;
+            *counter.entry(ESCAPE).or_insert(0) += f;
+        } else {
+            counter.insert(c as u16, f);
+        }
+    }
+    let tree = huffman_tree(&frequency_list(&counter));
+    let codes = huffman_codes(&tree);
+    let serialized_tree = serialize_tree(&tree, 9);
+    blob.extend((serialized_tree.len() as u16).to_le_bytes());
+    blob.extend(serialized_tree);
+
+    let mut writer = BitWriter::with_capacity(data.len() / 2);
+    for &c in data {
+        let symbol = if rare[c as usize] { ESCAPE } else { c as u16 };
+        for &bit in &codes[&symbol] {
+            writer.write_bit(bit == b'1');
+        }
+        if symbol == ESCAPE {
+            writer.write_bits(c as u64, 8);
+        }
+    }
+    blob.extend(writer.finish().0);
+    blob
+}
+
+/// Restores data written by [`compress_with_escape`].
+pub fn decompress_with_escape(blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (header, blob) = read_header(blob, ESCAPE_MAGIC, FLAG_CHECKSUM)?;
+    if header.len == 0 {
+        return verify_checksum(Vec::new(), header.checksum);
+    }
+
+    let (serialized_tree, bitstream) = split_table(blob)?;
+    let tree = deserialize_tree::<u16>(serialized_tree, 9)?;
+    let tree = decode_tree_for(&tree);
+
+    // Each byte takes at least one bit, which bounds a corrupt length.
+    let mut data = Vec::with_capacity(header.len.min(bitstream.len() * 8));
+    let mut bits = BitReader::new(bitstream);
+    let mut node = &*tree;
+    while data.len() < header.len {
+        let bit = bits.read_bit().ok_or(DecodeError::UnexpectedEof)?;
+        match decode_step(&tree, &mut node, bit)? {
+            Some(ESCAPE) => {
+                let c = bits.read_bits(8).ok_or(DecodeError::UnexpectedEof)?;
+                data.push(c as u8);
+            }
+            Some(c) => data.push(u8::try_from(c).map_err(|_| DecodeError::BadHeader)?),
+            None => {}
+        }
+    }
+    verify_checksum(data, header.checksum)
+}
+
+// `len` is the number of coded bytes and `data` the original data, whose
+// checksum is stored if `flags` asks for one.
+fn write_header(magic: &[u8; 4], flags: u8, len: usize, data: &[u8]) -> Vec<u8> {
+    let checksum = if flags & FLAG_CHECKSUM != 0 {
+        crc32(data)
+    } else {
+        0
+    };
+    write_header_with_checksum(magic, flags, len, checksum)
+}
+
+// `write_header` for a checksum computed elsewhere, e.g. while streaming.
+fn write_header_with_checksum(magic: &[u8; 4], flags: u8, len: usize, checksum: u32) -> Vec<u8> {
+    let mut blob = magic.to_vec();
+    blob.push(flags);
+    blob.extend((len as u64).to_le_bytes());
+    if flags & FLAG_CHECKSUM != 0 {
+        blob.extend(checksum.to_le_bytes());
+    }
+    blob
+}
+
+fn write_table_and_bitstream(
+    blob: &mut Vec<u8>,
+    table: &[u8],
+    data: &[u8],
+    codes: &HuffmanCode,
+    order: BitOrder,
+) {
+    blob.extend((table.len() as u16).to_le_bytes());
+    blob.extend(table);
+    let mut writer = BitWriter::with_capacity(data.len() / 2).with_bit_order(order);
+    let Ok(()) = write_byte_codes(data, codes, &mut writer) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    blob.extend(writer.finish().0);
+}
+
+fn bit_order(flags: u8) -> BitOrder {
+    if flags & FLAG_LSB_FIRST != 0 {
+        BitOrder::LsbFirst
+    } else {
+        BitOrder::MsbFirst
+    }
+}
+
+struct Header {
+    flags: u8,
+    // Number of coded bytes.
+    len: usize,
+    checksum: Option<u32>,
+}
+
+// Returns the header and the rest of the blob. Flags outside `known_flags` are
+// rejected.
+fn read_header<'a>(
+    blob: &'a [u8],
+    magic: &[u8; 4],
+    known_flags: u8,
+) -> Result<(Header, &'a [u8]), DecodeError> {
+    let blob = blob.strip_prefix(magic).ok_or(DecodeError::BadHeader)?;
+    let (&flags, blob) = blob.split_first().ok_or(DecodeError::BadHeader)?;
+    if flags & !known_flags != 0 {
+        return Err(DecodeError::BadHeader);
+    }
+    let (len, blob) = blob
+        .split_first_chunk::<8>()
+        .ok_or(DecodeError::BadHeader)?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| DecodeError::BadHeader)?;
+    let (checksum, blob) = if flags & FLAG_CHECKSUM != 0 {
+        let (checksum, blob) = blob
+            .split_first_chunk::<4>()
+            .ok_or(DecodeError::BadHeader)?;
+        (Some(u32::from_le_bytes(*checksum)), blob)
+    } else {
+        (None, blob)
+    };
+    let header = Header {
+        flags,
+        len,
+        checksum,
+    };
+    Ok((header, blob))
+}
+
+// Splits the length-prefixed table from the bitstream.
+fn split_table(blob: &[u8]) -> Result<(&[u8], &[u8]), DecodeError> {
+    let (table_len, blob) = blob
+        .split_first_chunk::<2>()
+        .ok_or(DecodeError::BadHeader)?;
+    let table_len = u16::from_le_bytes(*table_len) as usize;
+    if blob.len() < table_len {
+        return Err(DecodeError::TruncatedTree);
+    }
+    Ok(blob.split_at(table_len))
+}
+
+// `len` copies of `c`, failing rather than aborting if a corrupt header asks
+// for more than can be allocated.
+fn repeat_byte(c: u8, len: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| DecodeError::BadHeader)?;
+    data.resize(len, c);
+    Ok(data)
+}
+
+fn verify_checksum<T: AsRef<[u8]>>(data: T, checksum: Option<u32>) -> Result<T, DecodeError> {
+    if checksum.is_some_and(|checksum| crc32(data.as_ref()) != checksum) {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+    Ok(data)
+}
+
+/// Splits `data` into blocks of `block_size` bytes and compresses each one
+/// on its own, so every block gets a tree fitted to its contents and blocks
+/// can be decoded independently. Each block is framed as
+/// `| compressed length: u64 | compressed block |`. Panics if `block_size`
+/// is 0.
+pub fn compress_blocks(data: &[u8], block_size: usize) -> Vec<u8> {
+    compress_blocks_with(data, block_size, compress)
+}
+
+// `compress_blocks` with each block compressed by `compress_block` instead of
+// `compress`, which must produce something `decompress` reads.
+pub(crate) fn compress_blocks_with(
+    data: &[u8],
+    block_size: usize,
+    compress_block: impl Fn(&[u8]) -> Vec<u8>,
+) -> Vec<u8> {
+    assert!(block_size > 0, "Block size must be positive");
+    frame_blocks(data.chunks(block_size).map(compress_block))
+}
+
+/// Same output as [`compress_blocks`], with the blocks compressed in
+/// parallel on the rayon thread pool. Panics if `block_size` is 0.
+#[cfg(feature = "rayon")]
+pub fn compress_blocks_parallel(data: &[u8], block_size: usize) -> Vec<u8> {
+    use rayon::prelude::*;
+
+    assert!(block_size > 0, "Block size must be positive");
+    let blocks = data
+        .par_chunks(block_size)
+        .map(compress)
+        .collect::<Vec<_>>();
+    frame_blocks(blocks)
+}
+
+// Concatenates compressed blocks, each prefixed with its length.
+fn frame_blocks(blocks: impl IntoIterator<Item = Vec<u8>>) -> Vec<u8> {
+    let mut blob = Vec::new();
+    for compressed in blocks {
+        blob.extend((compressed.len() as u64).to_le_bytes());
+        blob.extend(compressed);
+    }
+    blob
+}
+
+/// Restores data written by [`compress_blocks`].
+pub fn decompress_blocks(mut blob: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let mut data = Vec::new();
+    while !blob.is_empty() {
+        let (block_len, rest) = blob
+            .split_first_chunk::<8>()
+            .ok_or(DecodeError::BadHeader)?;
+        let block_len = usize::try_from(u64::from_le_bytes(*block_len))
+            .ok()
+            .filter(|&len| len <= rest.len())
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let (block, rest) = rest.split_at(block_len);
+        data.extend_from_slice(&decompress(block)?);
+        blob = rest;
+    }
+    Ok(data)
+}
+
+/// Writes the output of [`compress`] to `out` as it is produced, so only the
+/// input has to be held in memory.
+#[cfg(feature = "std")]
+pub fn encode_to_writer<W: Write>(data: &[u8], out: &mut W) -> io::Result<()> {
+    if data.is_empty() {
+        out.write_all(&write_header(MAGIC, FLAG_CHECKSUM, 0, data))?;
+        return out.flush();
+    }
+
+    let frequency_list = byte_frequency_list(data);
+    if let [(c, _)] = frequency_list[..] {
+        out.write_all(&write_header(
+            MAGIC,
+            FLAG_CHECKSUM | FLAG_SINGLE,
+            data.len(),
+            data,
+        ))?;
+        out.write_all(&[c])?;
+        return out.flush();
+    }
+    let tree = huffman_tree(&frequency_list);
+    let table = serialize_tree(&tree, 8);
+    let codes = huffman_codes(&tree);
+    if should_store(&frequency_list, &codes, table.len()) {
+        out.write_all(&write_header(
+            MAGIC,
+            FLAG_CHECKSUM | FLAG_STORED,
+            data.len(),
+            data,
+        ))?;
+        out.write_all(data)?;
+        return out.flush();
+    }
+
+    out.write_all(&write_header(MAGIC, FLAG_CHECKSUM, data.len(), data))?;
+    out.write_all(&(table.len() as u16).to_le_bytes())?;
+    out.write_all(&table)?;
+    let mut writer = HuffmanWriter::new(out, &codes);
+    // The writer buffers the bits of each call, so feed it in chunks.
+    for chunk in data.chunks(8 * 1024) {
+        writer.write_all(chunk)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Compresses the file at `input` into `output` with [`compress`].
+#[cfg(feature = "std")]
+pub fn compress_file<P: AsRef<Path>>(input: P, output: P) -> io::Result<()> {
+    let data = fs::read(input)?;
+    encode_to_writer(&data, &mut BufWriter::new(File::create(output)?))
+}
+
+/// Restores a file written by [`compress_file`]. Malformed input is reported
+/// as an [`io::ErrorKind::InvalidData`] error wrapping the [`DecodeError`].
+#[cfg(feature = "std")]
+pub fn decompress_file<P: AsRef<Path>>(input: P, output: P) -> io::Result<()> {
+    let blob = fs::read(input)?;
+    let data = decompress(&blob).map_err(invalid_data)?;
+    fs::write(output, data)
+}
+
+/// Compresses everything read from `input` into `output`, writing the same
+/// blob as [`compress`] with memory bounded by a fixed-size buffer. Coding
+/// needs the byte counts before the first code is written, so the input is
+/// copied to a temporary file while it is counted and read back from there,
+/// which suits pipes; [`compress_seekable`] reads a seekable source twice
+/// instead.
+#[cfg(feature = "std")]
+pub fn compress_stream<R: Read, W: Write>(input: R, output: W) -> io::Result<()> {
+    let spill = SpillFile::create()?;
+    let mut file = &spill.file;
+    let (model, checksum) = count_stream(input, |chunk| file.write_all(chunk))?;
+    file.seek(SeekFrom::Start(0))?;
+    encode_stream(file, output, &model, checksum)
+}
+
+/// [`compress_stream`] for a source that can be rewound, such as a [`File`]:
+/// it is read once from its current position to count the bytes and once
+/// more to code them, without a temporary copy.
+#[cfg(feature = "std")]
+pub fn compress_seekable<R: Read + Seek, W: Write>(mut input: R, output: W) -> io::Result<()> {
+    let start = input.stream_position()?;
+    let (model, checksum) = count_stream(&mut input, |_| Ok(()))?;
+    input.seek(SeekFrom::Start(start))?;
+    encode_stream(input, output, &model, checksum)
+}
+
+/// Restores a blob written by [`compress`] or [`compress_stream`] from
+/// `input` to `output` as it is decoded, with memory bounded by the tree and
+/// a fixed-size buffer. Blobs from [`compress_rle`] and [`compress_mtf`] are
+/// undone in memory. Malformed input is reported as in [`decompress_file`].
+#[cfg(feature = "std")]
+pub fn decompress_stream<R: Read, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let known_flags =
+        FLAG_CHECKSUM | FLAG_RLE | FLAG_MTF | FLAG_STORED | FLAG_LSB_FIRST | FLAG_SINGLE;
+    let mut input = BufReader::with_capacity(STREAM_CHUNK, input);
+    // Magic, flags, length and then the checksum if there is one.
+    let mut blob = vec![0; 13];
+    read_exact_or(&mut input, &mut blob, DecodeError::BadHeader)?;
+    if blob[4] & FLAG_CHECKSUM != 0 {
+        blob.resize(17, 0);
+        read_exact_or(&mut input, &mut blob[13..], DecodeError::BadHeader)?;
+    }
+    let (header, _) = read_header(&blob, MAGIC, known_flags).map_err(invalid_data)?;
+    if header.flags & (FLAG_RLE | FLAG_MTF) != 0 {
+        input.read_to_end(&mut blob)?;
+        output.write_all(&decompress(&blob).map_err(invalid_data)?)?;
+        return output.flush();
+    }
+
+    let mut crc = Crc32::new();
+    let mut remaining = header.len;
+    if header.flags & FLAG_SINGLE != 0 {
+        let mut c = [0];
+        read_exact_or(&mut input, &mut c, DecodeError::UnexpectedEof)?;
+        let chunk = [c[0]; STREAM_CHUNK];
+        while remaining > 0 {
+            let chunk = &chunk[..remaining.min(STREAM_CHUNK)];
+            crc.update(chunk);
+            output.write_all(chunk)?;
+            remaining -= chunk.len();
+        }
+    } else if header.flags & FLAG_STORED != 0 {
+        while remaining > 0 {
+            let chunk = input.fill_buf()?;
+            if chunk.is_empty() {
+                return Err(invalid_data(DecodeError::UnexpectedEof));
+            }
+            let chunk = &chunk[..chunk.len().min(remaining)];
+            crc.update(chunk);
+            output.write_all(chunk)?;
+            let consumed = chunk.len();
+            input.consume(consumed);
+            remaining -= consumed;
+        }
+    } else if remaining > 0 {
+        let mut table_len = [0; 2];
+        read_exact_or(&mut input, &mut table_len, DecodeError::BadHeader)?;
+        let mut table = vec![0; u16::from_le_bytes(table_len) as usize];
+        read_exact_or(&mut input, &mut table, DecodeError::TruncatedTree)?;
+        let tree = deserialize_tree(&table, 8).map_err(invalid_data)?;
+        let tree = decode_tree_for(&tree);
+        let lsb_first = header.flags & FLAG_LSB_FIRST != 0;
+        let mut node = &*tree;
+        let mut decoded = Vec::with_capacity(STREAM_CHUNK);
+        while remaining > 0 {
+            let chunk = input.fill_buf()?;
+            if chunk.is_empty() {
+                return Err(invalid_data(DecodeError::UnexpectedEof));
+            }
+            let mut consumed = 0;
+            for &byte in chunk {
+                consumed += 1;
+                let byte = if lsb_first { byte.reverse_bits() } else { byte };
+                for shift in (0..8).rev() {
+                    let bit = byte >> shift & 1 == 1;
+                    if let Some(c) = decode_step(&tree, &mut node, bit).map_err(invalid_data)? {
+                        decoded.push(c);
+                        remaining -= 1;
+                        if remaining == 0 {
+                            break;
+                        }
+                    }
+                }
+                if remaining == 0 {
+                    break;
+                }
+            }
+            input.consume(consumed);
+            crc.update(&decoded);
+            output.write_all(&decoded)?;
+            decoded.clear();
+        }
+    }
+    if header
+        .checksum
+        .is_some_and(|checksum| crc.finish() != checksum)
+    {
+        return Err(invalid_data(DecodeError::ChecksumMismatch));
+    }
+    output.flush()
+}
+
+// Size of the buffers used by the streaming functions.
+#[cfg(feature = "std")]
+const STREAM_CHUNK: usize = 64 * 1024;
+
+// Counts the bytes of `input` and their checksum, handing each chunk to
+// `spill` as it is read.
+#[cfg(feature = "std")]
+fn count_stream<R: Read>(
+    mut input: R,
+    mut spill: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<(FrequencyModel, u32)> {
+    let mut model = FrequencyModel::new();
+    let mut crc = Crc32::new();
+    let mut chunk = vec![0; STREAM_CHUNK];
+    loop {
+        let read = match input.read(&mut chunk) {
+            Ok(0) => return Ok((model, crc.finish())),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        model.update(&chunk[..read]);
+        crc.update(&chunk[..read]);
+        spill(&chunk[..read])?;
+    }
+}
+
+// Second pass of `compress_stream`: writes the blob `encode_to_writer` would
+// for the bytes counted in `model`, reading them again from `input`.
+#[cfg(feature = "std")]
+fn encode_stream<R: Read, W: Write>(
+    input: R,
+    mut output: W,
+    model: &FrequencyModel,
+    checksum: u32,
+) -> io::Result<()> {
+    let histogram = model.histogram();
+    let len = histogram.total();
+    let frequency_list = histogram.iter().collect::<Vec<_>>();
+    if frequency_list.is_empty() {
+        output.write_all(&write_header_with_checksum(
+            MAGIC,
+            FLAG_CHECKSUM,
+            0,
+            checksum,
+        ))?;
+        return output.flush();
+    }
+    if let [(c, _)] = frequency_list[..] {
+        let flags = FLAG_CHECKSUM | FLAG_SINGLE;
+        output.write_all(&write_header_with_checksum(MAGIC, flags, len, checksum))?;
+        output.write_all(&[c])?;
+        return output.flush();
+    }
+
+    let tree = huffman_tree(&frequency_list);
+    let table = serialize_tree(&tree, 8);
+    let codes = huffman_codes(&tree);
+    let mut input = input.take(len as u64);
+    let copied = if should_store(&frequency_list, &codes, table.len()) {
+        let flags = FLAG_CHECKSUM | FLAG_STORED;
+        output.write_all(&write_header_with_checksum(MAGIC, flags, len, checksum))?;
+        let copied = io::copy(&mut input, &mut output)?;
+        output.flush()?;
+        copied
+    } else {
+        output.write_all(&write_header_with_checksum(
+            MAGIC,
+            FLAG_CHECKSUM,
+            len,
+            checksum,
+        ))?;
+        output.write_all(&(table.len() as u16).to_le_bytes())?;
+        output.write_all(&table)?;
+        let mut writer = HuffmanWriter::new(&mut output, &codes);
+        let copied = io::copy(&mut input, &mut writer)?;
+        writer.finish()?;
+        copied
+    };
+    if copied != len as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Input shrank between the two passes",
+        ));
+    }
+    Ok(())
+}
+
+// Temporary file holding the input of `compress_stream`, removed on drop.
+#[cfg(feature = "std")]
+struct SpillFile {
+    path: PathBuf,
+    file: File,
+}
+
+#[cfg(feature = "std")]
+impl SpillFile {
+    fn create() -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "huffman-coding-spill-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(SpillFile { path, file })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Malformed input as an `io::Error`, the form the `io` based functions report.
+#[cfg(feature = "std")]
+pub(crate) fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// `read_exact`, reporting a short read as `error`.
+#[cfg(feature = "std")]
+fn read_exact_or<R: Read>(input: &mut R, buf: &mut [u8], error: DecodeError) -> io::Result<()> {
+    input.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_data(error),
+        _ => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::path::PathBuf;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use alloc::vec;
+
+    #[cfg(feature = "std")]
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("huffman-coding-{}-{name}", std::process::id()))
+    }
+
+    // Fibonacci counts over 34 bytes, about 9 MB, whose tree is a chain with
+    // codes of up to 33 bits, too long to pack into a u32.
+    #[cfg(feature = "std")]
+    fn deep_tree_data() -> Vec<u8> {
+        (0..34u8)
+            .scan((1, 1), |(a, b), c| {
+                let count = *a;
+                (*a, *b) = (*b, *a + *b);
+                Some(vec![c; count])
+            })
+            .flatten()
+            .collect()
+    }
+
+    // `compress` of `deep_tree_data`, built once for the tests sharing it.
+    #[cfg(feature = "std")]
+    fn deep_tree_blob() -> &'static [u8] {
+        static BLOB: std::sync::OnceLock<Vec<u8>> = std::sync::OnceLock::new();
+        BLOB.get_or_init(|| compress(&deep_tree_data()))
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let inputs: [&[u8]; 5] = [b"", b"A", b"AAAA", b"AABCBAD", &random];
+        for data in inputs {
+            assert_eq!(decompress(&compress(data)).unwrap(), data);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_encode_to_writer() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..64 * 1024)
+            .map(|_| r.gen_range(0..16))
+            .collect::<Vec<u8>>();
+        let inputs: [&[u8]; 4] = [b"", b"A", b"AABCBAD", &random];
+        for data in inputs {
+            let mut out = Vec::new();
+            encode_to_writer(data, &mut out).unwrap();
+            assert_eq!(out, compress(data));
+        }
+
+        let path = temp_path("writer.huff");
+        let mut file = File::create(&path).unwrap();
+        encode_to_writer(&random, &mut file).unwrap();
+        drop(file);
+        assert_eq!(decompress(&fs::read(&path).unwrap()).unwrap(), random);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_encode_to_writer_long_codes() {
+        let data = deep_tree_data();
+        let tree = huffman_tree(&byte_frequency_list(&data));
+        assert_eq!(tree.depth(), 33);
+        let mut out = Vec::new();
+        encode_to_writer(&data, &mut out).unwrap();
+        assert_eq!(out, deep_tree_blob());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compress_file() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen_range(0..16)).collect::<Vec<u8>>();
+        for (name, data) in [("empty", &[][..]), ("random", &random)] {
+            let original = temp_path(&format!("{name}.txt"));
+            let compressed = temp_path(&format!("{name}.huff"));
+            let restored = temp_path(&format!("{name}.out"));
+            fs::write(&original, data).unwrap();
+
+            compress_file(&original, &compressed).unwrap();
+            decompress_file(&compressed, &restored).unwrap();
+            assert_eq!(fs::read(&restored).unwrap(), data);
+
+            for path in [original, compressed, restored] {
+                fs::remove_file(path).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compress_stream() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let skewed = (0..200 * 1024)
+            .map(|_| (r.gen::<f64>().powi(4) * 200.0) as u8)
+            .collect::<Vec<u8>>();
+        let inputs: [&[u8]; 5] = [b"", b"A", b"AABCBAD", &random, &skewed];
+        for data in inputs {
+            let mut streamed = Vec::new();
+            compress_stream(data, &mut streamed).unwrap();
+            assert_eq!(streamed, compress(data));
+
+            let mut seekable = Vec::new();
+            compress_seekable(io::Cursor::new(data), &mut seekable).unwrap();
+            assert_eq!(seekable, streamed);
+
+            let mut restored = Vec::new();
+            decompress_stream(&streamed[..], &mut restored).unwrap();
+            assert_eq!(restored, data);
+        }
+
+        // Other bit orders and transforms are read back too.
+        let blobs = [
+            compress_with_options(&skewed, true, None, BitOrder::LsbFirst),
+            compress_with_options(b"AABCBAD", false, None, BitOrder::MsbFirst),
+            compress_rle(&skewed),
+        ];
+        for blob in blobs {
+            let mut restored = Vec::new();
+            decompress_stream(&blob[..], &mut restored).unwrap();
+            assert_eq!(restored, decompress(&blob).unwrap().as_ref());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compress_stream_long_codes() {
+        let data = deep_tree_data();
+        let mut streamed = Vec::new();
+        compress_stream(&data[..], &mut streamed).unwrap();
+        assert_eq!(streamed, deep_tree_blob());
+        let mut seekable = Vec::new();
+        compress_seekable(io::Cursor::new(&data), &mut seekable).unwrap();
+        assert_eq!(seekable, deep_tree_blob());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decompress_stream_errors() {
+        let decode_error = |blob: &[u8]| {
+            let e = decompress_stream(blob, io::sink()).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            *e.into_inner().unwrap().downcast::<DecodeError>().unwrap()
+        };
+        let blob = compress(b"AABCBAD");
+        assert!(matches!(decode_error(&blob[..10]), DecodeError::BadHeader));
+        assert!(matches!(
+            decode_error(&blob[..20]),
+            DecodeError::TruncatedTree
+        ));
+        assert!(matches!(
+            decode_error(&blob[..blob.len() - 1]),
+            DecodeError::UnexpectedEof
+        ));
+        let mut corrupt = blob.clone();
+        corrupt[13] ^= 1;
+        assert!(matches!(
+            decode_error(&corrupt),
+            DecodeError::ChecksumMismatch
+        ));
+
+        let stored = compress(&(0..=255).collect::<Vec<u8>>());
+        assert!(matches!(
+            decode_error(&stored[..stored.len() - 1]),
+            DecodeError::UnexpectedEof
+        ));
+    }
+
+    // Reader yielding `len` skewed bytes without holding them, checksumming
+    // what it gives out.
+    #[cfg(feature = "std")]
+    struct Generator {
+        rng: StdRng,
+        remaining: usize,
+        crc: Crc32,
+    }
+
+    #[cfg(feature = "std")]
+    impl Read for Generator {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = buf.len().min(self.remaining);
+            for c in &mut buf[..read] {
+                *c = (self.rng.gen::<f64>().powi(4) * 200.0) as u8;
+            }
+            self.crc.update(&buf[..read]);
+            self.remaining -= read;
+            Ok(read)
+        }
+    }
+
+    // Writer checksumming what it is given instead of keeping it.
+    #[cfg(feature = "std")]
+    struct Checksummer {
+        len: usize,
+        crc: Crc32,
+    }
+
+    #[cfg(feature = "std")]
+    impl Write for Checksummer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.len += buf.len();
+            self.crc.update(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_large_file() {
+        // Neither side holds the 4 MiB, so memory stays at the stream
+        // buffers and the tree.
+        let len = 4 * 1024 * 1024;
+        let mut input = Generator {
+            rng: StdRng::seed_from_u64(42),
+            remaining: len,
+            crc: Crc32::new(),
+        };
+        let path = temp_path("large.huff");
+        compress_stream(&mut input, BufWriter::new(File::create(&path).unwrap())).unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < len as u64);
+
+        let mut output = Checksummer {
+            len: 0,
+            crc: Crc32::new(),
+        };
+        decompress_stream(File::open(&path).unwrap(), &mut output).unwrap();
+        assert_eq!(output.len, len);
+        assert_eq!(output.crc.finish(), input.crc.finish());
+
+        // Reading the compressed file in place gives the same blob.
+        let copy = temp_path("large-copy.huff");
+        compress_seekable(
+            File::open(&path).unwrap(),
+            BufWriter::new(File::create(&copy).unwrap()),
+        )
+        .unwrap();
+        let mut again = Vec::new();
+        compress_stream(File::open(&path).unwrap(), &mut again).unwrap();
+        assert_eq!(fs::read(&copy).unwrap(), again);
+
+        for path in [path, copy] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decompress_file_invalid() {
+        let input = temp_path("invalid.huff");
+        let output = temp_path("invalid.out");
+        fs::write(&input, b"not compressed").unwrap();
+        let error = decompress_file(&input, &output).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!output.exists());
+        fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_compress_header() {
+        let blob = compress(b"AABCBAD");
+        assert_eq!(&blob[..4], b"HUFF");
+        assert_eq!(blob[4], FLAG_CHECKSUM);
+        assert_eq!(&blob[5..13], &7u64.to_le_bytes());
+        assert_eq!(&blob[13..17], &crc32(b"AABCBAD").to_le_bytes());
+        assert_eq!(&blob[17..19], &5u16.to_le_bytes());
+        // 5 bytes of tree followed by the 13 bit stream.
+        assert_eq!(blob.len(), 19 + 5 + 2);
+    }
+
+    #[test]
+    fn test_decompress_garbage() {
+        assert!(matches!(
+            decompress(b"not a compressed blob"),
+            Err(DecodeError::BadHeader)
+        ));
+        assert!(matches!(decompress(b""), Err(DecodeError::BadHeader)));
+
+        // A corrupt length is an error, not an allocation of that size.
+        for blob in [compress(b"AABCBAD"), compress(&[b'x'; 100])] {
+            let mut corrupt = blob;
+            corrupt[5..13].copy_from_slice(&u64::MAX.to_le_bytes());
+            assert!(decompress(&corrupt).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decompress_truncated() {
+        let blob = compress(b"AABCBAD");
+        assert!(matches!(
+            decompress(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decompress(&blob[..21]),
+            Err(DecodeError::TruncatedTree)
+        ));
+        assert!(matches!(
+            decompress(&blob[..6]),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_decompress_huge_length() {
+        // A length far beyond the bitstream fails instead of allocating it.
+        let mut blob = compress(b"AABCBAD");
+        blob[5..13].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert!(matches!(decompress(&blob), Err(DecodeError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_compress_without_checksum() {
+        let data = b"AABCBAD";
+        let blob = compress_with_options(data, false, None, BitOrder::MsbFirst);
+        assert_eq!(blob[4], 0);
+        assert_eq!(blob.len(), compress(data).len() - 4);
+        assert_eq!(decompress(&blob).unwrap(), &data[..]);
+
+        let mut blob = compress(data);
+        blob[4] |= 0b1000_0000;
+        assert!(matches!(decompress(&blob), Err(DecodeError::BadHeader)));
+    }
+
+    #[test]
+    fn test_compress_rle() {
+        let runs = [b"A".repeat(500), b"B".repeat(500)].concat();
+        let blob = compress_rle(&runs);
+        assert_eq!(blob[4], FLAG_CHECKSUM | FLAG_RLE);
+        assert_eq!(decompress(&blob).unwrap(), runs);
+        assert!(blob.len() * 4 < compress(&runs).len());
+
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b""[..], b"AAAAAAAAAA", b"AABCBAD", &random] {
+            assert_eq!(decompress(&compress_rle(data)).unwrap(), data);
+        }
+
+        // Canonical blobs have no transforms.
+        let mut blob = compress_canonical(b"AABCBAD");
+        blob[4] |= FLAG_RLE;
+        assert!(matches!(
+            decompress_canonical(&blob),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_total_compressed_size() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let skewed = (0..4096)
+            .map(|_| (r.gen::<f64>().powi(4) * 200.0) as u8)
+            .collect::<Vec<u8>>();
+        let inputs: [&[u8]; 6] = [b"", b"AAAA", b"AABCBAD", b"0123456789", &random, &skewed];
+        for data in inputs {
+            assert_eq!(total_compressed_size(data), compress(data).len());
+        }
+
+        // Short input is mostly header and table, and ends up larger.
+        let size = total_compressed_size(b"0123456789");
+        assert!(size > 10);
+        let (bitstream, _, _) = crate::huffman_encode_bytes(b"0123456789");
+        assert!(size > 4 * bitstream.len());
+        assert!(total_compressed_size(&skewed) < skewed.len());
+    }
+
+    #[test]
+    fn test_compress_single_symbol() {
+        // Only the header and the repeated byte, whatever the length.
+        let data = vec![b'x'; 1024 * 1024];
+        let blob = compress(&data);
+        assert_eq!(blob[4], FLAG_CHECKSUM | FLAG_SINGLE);
+        assert_eq!(blob.len(), 18);
+        assert_eq!(blob[17], b'x');
+        assert_eq!(decompress(&blob).unwrap(), data);
+        assert_eq!(compress(b"x").len(), 18);
+
+        #[cfg(feature = "std")]
+        {
+            let mut out = Vec::new();
+            encode_to_writer(&data, &mut out).unwrap();
+            assert_eq!(out, blob);
+            let mut restored = Vec::new();
+            decompress_stream(&blob[..], &mut restored).unwrap();
+            assert_eq!(restored, data);
+        }
+
+        assert!(matches!(
+            decompress(&blob[..17]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        // A corrupt length too large to allocate is an error, not an abort.
+        let mut huge = blob.clone();
+        huge[5..13].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(decompress(&huge).is_err());
+    }
+
+    #[test]
+    fn test_compress_stored() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let blob = compress(&random);
+        assert_eq!(blob[4], FLAG_CHECKSUM | FLAG_STORED);
+        // Only the header is added.
+        assert_eq!(blob.len(), random.len() + 17);
+        assert_eq!(&blob[17..], random);
+        // Stored data is borrowed from the blob, coded data is decoded.
+        let decompressed = decompress(&blob).unwrap();
+        assert!(matches!(decompressed, Cow::Borrowed(_)));
+        assert!(core::ptr::eq(decompressed.as_ptr(), blob[17..].as_ptr()));
+        assert_eq!(decompressed, random);
+        assert!(matches!(
+            decompress(&compress(b"AABCBAD")).unwrap(),
+            Cow::Owned(_)
+        ));
+        assert!(matches!(
+            decompress(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+
+        // Skewed data, however short, is still coded.
+        assert_eq!(compress(b"AABCBAD")[4], FLAG_CHECKSUM);
+        let text = (0..4096)
+            .map(|_| r.sample(rand::distributions::Alphanumeric))
+            .collect::<Vec<u8>>();
+        assert_eq!(compress(&text)[4], FLAG_CHECKSUM);
+
+        #[cfg(feature = "std")]
+        {
+            let mut out = Vec::new();
+            encode_to_writer(&random, &mut out).unwrap();
+            assert_eq!(out, blob);
+        }
+    }
+
+    #[test]
+    fn test_compress_mtf() {
+        let mut r = StdRng::seed_from_u64(42);
+        let clustered = (0..500)
+            .flat_map(|_| core::iter::repeat_n(r.gen_range(b'a'..=b'z'), r.gen_range(2..6)))
+            .collect::<Vec<u8>>();
+        let blob = compress_mtf(&clustered);
+        assert_eq!(blob[4], FLAG_CHECKSUM | FLAG_MTF);
+        assert_eq!(decompress(&blob).unwrap(), clustered);
+        assert!(blob.len() < compress(&clustered).len());
+
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b""[..], b"AABCBAD", &random] {
+            assert_eq!(decompress(&compress_mtf(data)).unwrap(), data);
+        }
+
+        // Both transforms together are undone in reverse order.
+        let blob = compress_with_flags(&clustered, FLAG_CHECKSUM | FLAG_MTF | FLAG_RLE, None);
+        assert_eq!(decompress(&blob).unwrap(), clustered);
+    }
+
+    #[test]
+    fn test_compress_with_escape() {
+        // A few common letters and a long tail of bytes seen once.
+        let mut r = StdRng::seed_from_u64(42);
+        let mut data = (0..4096)
+            .map(|_| r.gen_range(b'a'..=b'h'))
+            .collect::<Vec<u8>>();
+        data.extend((0..=255).filter(|c| !(b'a'..=b'h').contains(c)));
+
+        let table_len = |blob: &[u8]| u16::from_le_bytes([blob[17], blob[18]]) as usize;
+        let escaped = compress_with_escape(&data, 2);
+        assert_eq!(decompress_with_escape(&escaped).unwrap(), data);
+        assert!(table_len(&escaped) * 10 < table_len(&compress(&data)));
+        assert!(escaped.len() < compress(&data).len());
+
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for threshold in [0, 1, 10, usize::MAX] {
+            for data in [&b""[..], b"A", b"AABCBAD", &random] {
+                let blob = compress_with_escape(data, threshold);
+                assert_eq!(decompress_with_escape(&blob).unwrap(), data);
+            }
+        }
+
+        let blob = compress_with_escape(b"AABCBAD", 2);
+        assert!(matches!(
+            decompress_with_escape(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decompress_with_escape(&compress(b"AABCBAD")),
+            Err(DecodeError::BadHeader)
+        ));
+
+        // A corrupt length fails instead of allocating that many bytes.
+        let mut corrupt = blob.clone();
+        corrupt[5..13].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert!(matches!(
+            decompress_with_escape(&corrupt),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_compress_max_code_length() {
+        // Fibonacci frequencies make the Huffman tree as deep as possible.
+        let (mut a, mut b) = (1, 1);
+        let mut data = Vec::new();
+        for c in b'A'..=b'P' {
+            data.extend(core::iter::repeat_n(c, a));
+            (a, b) = (b, a + b);
+        }
+        let (_, tree, _) = crate::huffman_encode_with_tree(&data);
+        assert_eq!(tree.depth(), 15);
+
+        let blob = compress_with_options(&data, true, Some(8), BitOrder::MsbFirst);
+        assert_eq!(decompress(&blob).unwrap(), data);
+        let table = split_table(&blob[17..]).unwrap().0;
+        assert!(deserialize_tree::<u8>(table, 8).unwrap().depth() <= 8);
+        assert_eq!(
+            compress_with_options(&data, true, Some(15), BitOrder::MsbFirst),
+            compress(&data)
+        );
+    }
+
+    #[test]
+    fn test_decompress_checksum_mismatch() {
+        let mut blob = compress(b"AABCBAD");
+        // Turns the final D (011) into a B (00) followed by padding.
+        blob[25] ^= 0b0001_1000;
+        assert!(matches!(
+            decompress(&blob),
+            Err(DecodeError::ChecksumMismatch)
+        ));
+
+        let mut blob = compress(b"");
+        blob[13] ^= 1;
+        assert!(matches!(
+            decompress(&blob),
+            Err(DecodeError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_compress_blocks_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..1000).map(|_| r.gen_range(0..16)).collect::<Vec<u8>>();
+        for block_size in [1, 7, 999, 1000, 1001, 4096] {
+            let blob = compress_blocks(&data, block_size);
+            assert_eq!(decompress_blocks(&blob).unwrap(), data);
+        }
+        assert!(compress_blocks(b"", 16).is_empty());
+        assert!(decompress_blocks(b"").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_compress_blocks_parallel() {
+        let mut r = StdRng::seed_from_u64(42);
+        let skewed = (0..300_000)
+            .map(|_| (r.gen::<f64>().powi(4) * 200.0) as u8)
+            .collect::<Vec<u8>>();
+        let random = (0..20_000).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b""[..], b"AABCBAD", &skewed, &random] {
+            for block_size in [7, 4096, 64 * 1024, 1 << 20] {
+                let blob = compress_blocks_parallel(data, block_size);
+                assert_eq!(blob, compress_blocks(data, block_size));
+                assert_eq!(decompress_blocks(&blob).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compress_blocks_are_independent() {
+        let data = [b"A".repeat(64), b"B".repeat(64)].concat();
+        let blob = compress_blocks(&data, 64);
+        let first_len = u64::from_le_bytes(blob[..8].try_into().unwrap()) as usize;
+        let first = &blob[8..8 + first_len];
+        assert_eq!(decompress(first).unwrap(), b"A".repeat(64));
+        assert_eq!(
+            decompress(&blob[16 + first_len..]).unwrap(),
+            b"B".repeat(64)
+        );
+    }
+
+    #[test]
+    fn test_decompress_blocks_truncated() {
+        let blob = compress_blocks(b"AABCBAD", 4);
+        assert!(matches!(
+            decompress_blocks(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decompress_blocks(&blob[..4]),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_compress_canonical_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        // Fibonacci frequencies give Huffman codes longer than 15 bits.
+        let fibonacci = (0..20u8)
+            .scan((1, 1), |(a, b), c| {
+                let count = *a;
+                (*a, *b) = (*b, *a + *b);
+                Some(vec![c; count])
+            })
+            .flatten()
+            .collect::<Vec<u8>>();
+        let inputs: [&[u8]; 6] = [b"", b"A", b"AAAA", b"AABCBAD", &random, &fibonacci];
+        for data in inputs {
+            assert_eq!(
+                decompress_canonical(&compress_canonical(data)).unwrap(),
+                data
+            );
+        }
+        assert!(matches!(
+            decompress_canonical(&compress(b"AABCBAD")),
+            Err(DecodeError::BadHeader)
+        ));
+
+        // A corrupt length fails instead of allocating that many bytes.
+        let mut blob = compress_canonical(b"AABCBAD");
+        blob[5..13].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert!(matches!(
+            decompress_canonical(&blob),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_compress_canonical_header() {
+        let table_len = |blob: &[u8]| u16::from_le_bytes([blob[17], blob[18]]);
+        // Four lengths of 4 bits after a run of 65 missing characters take as
+        // many bytes as the tree.
+        assert_eq!(table_len(&compress_canonical(b"AABCBAD")), 4);
+        assert_eq!(table_len(&compress(b"AABCBAD")), 5);
+
+        let mut r = StdRng::seed_from_u64(42);
+        let text = (0..4096)
+            .map(|_| r.sample(rand::distributions::Alphanumeric))
+            .collect::<Vec<u8>>();
+        assert_eq!(table_len(&compress_canonical(&text)), 36);
+        assert_eq!(table_len(&compress(&text)), 78);
+
+        let all = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(table_len(&compress_canonical(&all)), 128);
+        let tree = huffman_tree(&byte_frequency_list(&all));
+        assert_eq!(serialize_tree(&tree, 8).len(), 320);
+        // The tree alone outweighs the data, so `compress` stores it.
+        assert_eq!(compress(&all)[4], FLAG_CHECKSUM | FLAG_STORED);
+    }
+
+    #[test]
+    fn test_compress_with_eof_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let inputs: [&[u8]; 5] = [b"", b"A", b"AAAA", b"AABCBAD", &random];
+        for data in inputs {
+            assert_eq!(decompress_with_eof(&compress_with_eof(data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decompress_with_eof_ignores_trailing_bits() {
+        let blob = compress_with_eof(b"AABCBAD");
+        for padding in [&[0x00][..], &[0xff], &[0x55; 16]] {
+            let padded = [&blob[..], padding].concat();
+            assert_eq!(decompress_with_eof(&padded).unwrap(), b"AABCBAD");
+        }
+        assert!(matches!(
+            decompress_with_eof(&blob[..blob.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+}
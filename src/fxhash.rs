@@ -0,0 +1,81 @@
+// The multiply-rotate hash used by rustc ("FxHash"). It is much cheaper than
+// SipHash for small keys such as bytes, but gives no protection against
+// crafted collisions, so it is only used for maps built from our own codes.
+
+use core::hash::{BuildHasherDefault, Hasher};
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+pub(crate) type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    #[inline]
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for &b in chunks.remainder() {
+            self.add(b as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, hash::BuildHasher};
+
+    use super::*;
+
+    #[test]
+    fn test_fx_hasher_distinguishes_bytes() {
+        let hashes = (0..=255u8)
+            .map(|b| FxBuildHasher::default().hash_one(b))
+            .collect::<HashSet<_>>();
+        assert_eq!(hashes.len(), 256);
+        assert_eq!(
+            FxBuildHasher::default().hash_one(b"AABCBAD"),
+            FxBuildHasher::default().hash_one(b"AABCBAD")
+        );
+    }
+}
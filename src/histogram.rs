@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+
+use crate::{frequency_array, huffman_tree, HuffmanTreeNode, Map};
+
+/// Byte counts of a message, iterated in the same order as
+/// [`frequency_list`](crate::frequency_list).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [usize; 256],
+    total: usize,
+}
+
+impl Histogram {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Histogram {
+            counts: frequency_array(data),
+            total: data.len(),
+        }
+    }
+
+    /// How many times `byte` occurs.
+    pub fn count(&self, byte: u8) -> usize {
+        self.counts[byte as usize]
+    }
+
+    /// Number of bytes counted.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The byte with the highest count, the smallest one on ties, or `None`
+    /// if nothing was counted.
+    pub fn most_frequent(&self) -> Option<u8> {
+        self.iter().next().map(|(c, _)| c)
+    }
+
+    /// Bytes that occur at least once with their counts, by decreasing count
+    /// and then by byte.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, usize)> {
+        let mut counts = (0..=255)
+            .zip(self.counts)
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        // The array is in byte order, so a stable sort on the count alone
+        // keeps ties ordered.
+        counts.sort_by(|(_, count_1), (_, count_2)| count_2.cmp(count_1));
+        counts.into_iter()
+    }
+
+    /// The counts as weights for [`huffman_tree_from_weights`](crate::huffman_tree_from_weights).
+    pub fn weights(&self) -> Map<u8, usize> {
+        self.iter().collect()
+    }
+}
+
+/// Byte counts accumulated over several pieces of input, e.g. successive
+/// reads of a file, so the tree can be built once at the end without holding
+/// the whole input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequencyModel {
+    counts: [usize; 256],
+}
+
+impl Default for FrequencyModel {
+    fn default() -> Self {
+        FrequencyModel { counts: [0; 256] }
+    }
+}
+
+impl FrequencyModel {
+    pub fn new() -> Self {
+        FrequencyModel::default()
+    }
+
+    /// Adds the bytes of `data` to the counts.
+    pub fn update(&mut self, data: &[u8]) {
+        for &c in data {
+            self.counts[c as usize] += 1;
+        }
+    }
+
+    /// The counts so far.
+    pub fn histogram(&self) -> Histogram {
+        Histogram {
+            counts: self.counts,
+            total: self.counts.iter().sum(),
+        }
+    }
+
+    /// Tree for everything counted so far, or `None` if nothing was.
+    pub fn tree(&self) -> Option<HuffmanTreeNode> {
+        let frequency_list = self.histogram().iter().collect::<Vec<_>>();
+        (!frequency_list.is_empty()).then(|| huffman_tree(&frequency_list))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frequency_counter, frequency_list, huffman_tree, huffman_tree_from_weights};
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::from_bytes(b"AABCBAD");
+        assert_eq!(histogram.most_frequent(), Some(b'A'));
+        assert_eq!(histogram.total(), 7);
+        assert_eq!(histogram.count(b'B'), 2);
+        assert_eq!(histogram.count(b'Z'), 0);
+        assert_eq!(
+            histogram.iter().collect::<Vec<_>>(),
+            [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]
+        );
+        assert_eq!(histogram.weights(), frequency_counter(b"AABCBAD"));
+        assert_eq!(
+            huffman_tree_from_weights(&histogram.weights()).unwrap(),
+            huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")))
+        );
+
+        let empty = Histogram::from_bytes(b"");
+        assert_eq!(empty.most_frequent(), None);
+        assert_eq!(empty.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_frequency_model() {
+        let mut model = FrequencyModel::new();
+        assert_eq!(model.tree(), None);
+        model.update(b"AAB");
+        model.update(b"");
+        model.update(b"CD");
+        assert_eq!(model.histogram(), Histogram::from_bytes(b"AABCD"));
+        assert_eq!(
+            model.tree(),
+            Some(huffman_tree(&frequency_list(&frequency_counter(b"AABCD"))))
+        );
+    }
+}
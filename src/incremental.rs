@@ -0,0 +1,131 @@
+use alloc::vec::Vec;
+
+use crate::{build_decode_tree, decode_step, huffman_codes, DecodeError, HuffmanTreeNode};
+
+/// Decoder fed one byte at a time, e.g. as packets arrive. A code split
+/// across two bytes is completed by the next [`push`](Self::push).
+///
+/// ```
+/// use huffman_coding::{huffman_build, IncrementalDecoder};
+///
+/// let model = huffman_build(b"AABCBAD");
+/// let (encoded, _) = model.encode(b"AABCBAD").unwrap();
+/// let mut decoder = IncrementalDecoder::new(model.tree().clone(), 7);
+/// let mut decoded = Vec::new();
+/// for &byte in &encoded {
+///     decoded.extend(decoder.push(byte).unwrap());
+/// }
+/// decoder.finish().unwrap();
+/// assert_eq!(decoded, b"AABCBAD");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncrementalDecoder {
+    tree: HuffmanTreeNode,
+    // Bits of the code read so far, the path from the root to the current
+    // node.
+    cursor: Vec<bool>,
+    remaining: usize,
+}
+
+impl IncrementalDecoder {
+    /// Decoder for `len` characters coded with `tree`.
+    pub fn new(tree: HuffmanTreeNode, len: usize) -> Self {
+        // A single leaf decodes one character per `1` bit, as in `decode_bits`.
+        let tree = match tree {
+            HuffmanTreeNode::Character(_) => build_decode_tree(&huffman_codes(&tree)),
+            tree => tree,
+        };
+        IncrementalDecoder {
+            tree,
+            cursor: Vec::new(),
+            remaining: len,
+        }
+    }
+
+    /// Number of characters still to be decoded.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Decodes the bits of `byte`, returning the characters it completes.
+    /// Once every character is decoded, further bits are padding and ignored.
+    pub fn push(&mut self, byte: u8) -> Result<Vec<u8>, DecodeError> {
+        let mut node = &self.tree;
+        for &bit in &self.cursor {
+            decode_step(&self.tree, &mut node, bit)?;
+        }
+        let mut decoded = Vec::new();
+        for shift in (0..8).rev() {
+            if self.remaining == 0 {
+                break;
+            }
+            let bit = byte >> shift & 1 == 1;
+            if let Some(c) = decode_step(&self.tree, &mut node, bit)? {
+                decoded.push(c);
+                self.remaining -= 1;
+                self.cursor.clear();
+            } else {
+                self.cursor.push(bit);
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Checks that every character was decoded.
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.remaining > 0 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{decode_with_tree, huffman_encode_with_tree};
+
+    fn decode_bytewise(encoded: &[u8], tree: &HuffmanTreeNode, len: usize) -> Vec<u8> {
+        let mut decoder = IncrementalDecoder::new(tree.clone(), len);
+        let mut decoded = Vec::new();
+        for &byte in encoded {
+            decoded.extend(decoder.push(byte).unwrap());
+        }
+        decoder.finish().unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_incremental_decoder_matches_decode() {
+        let mut r = StdRng::seed_from_u64(42);
+        let skewed = (0..4096)
+            .map(|_| (r.gen::<f64>().powi(4) * 200.0) as u8)
+            .collect::<Vec<u8>>();
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b"AABCBAD"[..], b"AAAA", &skewed, &random] {
+            let (encoded, tree, _) = huffman_encode_with_tree(data);
+            let (expected, _) = decode_with_tree(&encoded, &tree, data.len()).unwrap();
+            assert_eq!(decode_bytewise(&encoded, &tree, data.len()), expected);
+        }
+    }
+
+    #[test]
+    fn test_incremental_decoder_split_code() {
+        // A has a 1-bit code, so the code of C starts in the first byte and
+        // ends in the second. Only 8 characters are asked for, so the rest is
+        // ignored.
+        let (encoded, tree, _) = huffman_encode_with_tree(b"AAAAAAACBBD");
+        let mut decoder = IncrementalDecoder::new(tree, 8);
+        assert_eq!(decoder.push(encoded[0]).unwrap(), b"AAAAAAA");
+        assert_eq!(decoder.remaining(), 1);
+        assert!(matches!(
+            decoder.clone().finish(),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert_eq!(decoder.push(encoded[1]).unwrap(), b"C");
+        assert_eq!(decoder.push(encoded[1]).unwrap(), b"");
+        decoder.finish().unwrap();
+    }
+}
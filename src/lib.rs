@@ -1,13 +1,147 @@
-use std::{collections::HashMap, iter::once};
+#![cfg_attr(not(feature = "std"), no_std)]
 
-fn frequency_counter(text: &String) -> HashMap<u8, usize> {
-    text.as_bytes().iter().fold(HashMap::new(), |mut acc, &c| {
+extern crate alloc;
+// The test harness needs std even when the library does without it.
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+mod adaptive;
+pub mod bitio;
+mod checksum;
+mod const_table;
+#[cfg(feature = "serde")]
+mod decode_table;
+mod dot;
+mod encoder;
+mod error;
+mod fast_decode;
+mod format;
+#[cfg(feature = "fast-hash")]
+mod fxhash;
+mod histogram;
+mod incremental;
+#[cfg(test)]
+mod round_trip;
+mod static_table;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod stream;
+mod transform;
+
+pub use adaptive::{adaptive_decode, adaptive_encode};
+pub use const_table::ConstCodeTable;
+#[cfg(feature = "serde")]
+pub use decode_table::DecodeTable;
+pub use dot::to_dot;
+pub use encoder::{HuffmanEncoder, HuffmanEncoderBuilder};
+pub use error::{DecodeError, EncodeError, ValidationError};
+pub use fast_decode::{build_fast_decode_table, FastDecodeTable};
+#[cfg(feature = "rayon")]
+pub use format::compress_blocks_parallel;
+pub use format::{
+    compress, compress_blocks, compress_canonical, compress_mtf, compress_rle, compress_with_eof,
+    compress_with_escape, decompress, decompress_blocks, decompress_canonical, decompress_with_eof,
+    decompress_with_escape, total_compressed_size,
+};
+#[cfg(feature = "std")]
+pub use format::{
+    compress_file, compress_seekable, compress_stream, decompress_file, decompress_stream,
+    encode_to_writer,
+};
+pub use histogram::{FrequencyModel, Histogram};
+pub use incremental::IncrementalDecoder;
+pub use static_table::{
+    decode_static, decompress_with_model, encode_static, english_codes, ENGLISH_FREQUENCIES,
+};
+#[cfg(feature = "std")]
+pub use stats::{
+    average_code_length, compression_ratio, encode_stats, entropy, estimated_encoded_bytes,
+    frequency_report, per_symbol_savings, redundancy, EncodeStats,
+};
+#[cfg(feature = "std")]
+pub use stream::{frequency_counter_reader, HuffmanReader, HuffmanWriter};
+pub use transform::{mtf_decode, mtf_encode};
+
+use alloc::{
+    borrow::Cow, boxed::Box, collections::BinaryHeap, format, string::String, vec, vec::Vec,
+};
+use bitio::{BitReader, BitWriter};
+use core::{cmp::Ordering, fmt, hash::Hash};
+
+/// Map used throughout the API: a `HashMap` with the `std` feature, and a
+/// `BTreeMap` without it.
+#[cfg(feature = "std")]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// Anything that can be used as a character of the alphabet. Bytes are the
+/// default, but wider values such as `u16` samples or `char`s work as well.
+pub trait Symbol: Eq + Hash + Copy + Ord {}
+
+impl<T: Eq + Hash + Copy + Ord> Symbol for T {}
+
+/// Counts how many times each character occurs in `data`.
+pub fn frequency_counter<S: Symbol>(data: &[S]) -> Map<S, usize> {
+    data.iter().fold(Map::new(), |mut acc, &c| {
         *acc.entry(c).or_insert(0) += 1;
         acc
     })
 }
 
-fn frequency_list(frequency_counter: &HashMap<u8, usize>) -> Vec<(u8, usize)> {
+/// Sums two histograms character by character, e.g. to build one model from
+/// the counts of several files.
+pub fn combine_histograms<S: Symbol>(a: &Map<S, usize>, b: &Map<S, usize>) -> Map<S, usize> {
+    b.iter().fold(a.clone(), |mut acc, (&c, &f)| {
+        *acc.entry(c).or_insert(0) += f;
+        acc
+    })
+}
+
+// Byte histogram indexed by the byte, which avoids hashing on the hot path.
+fn frequency_array(data: &[u8]) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for &c in data {
+        counts[c as usize] += 1;
+    }
+    counts
+}
+
+// Same list as `frequency_list(&frequency_counter(data))`.
+fn byte_frequency_list(data: &[u8]) -> Vec<(u8, usize)> {
+    Histogram::from_bytes(data).iter().collect()
+}
+
+/// Same result as [`frequency_counter`] for bytes, counting chunks of `data`
+/// on the rayon thread pool and summing their histograms.
+#[cfg(feature = "rayon")]
+pub fn par_frequency_counter(data: &[u8]) -> Map<u8, usize> {
+    use rayon::prelude::*;
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+    let counts = data.par_chunks(CHUNK_SIZE).map(frequency_array).reduce(
+        || [0; 256],
+        |mut acc, counts| {
+            for (total, count) in acc.iter_mut().zip(counts) {
+                *total += count;
+            }
+            acc
+        },
+    );
+    (0..=255)
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .collect()
+}
+
+/// Flattens a histogram into `(character, frequency)` pairs sorted by
+/// frequency, highest first, with ties broken by the smaller character.
+///
+/// This order is guaranteed: it is the only input to [`huffman_tree`], so two
+/// histograms with the same counts always produce the same tree and codes,
+/// whatever order the map iterates in.
+pub fn frequency_list<S: Symbol>(frequency_counter: &Map<S, usize>) -> Vec<(S, usize)> {
     let mut frequency_list = frequency_counter
         .iter()
         .map(|(c, f)| (*c, *f))
@@ -18,103 +152,557 @@ fn frequency_list(frequency_counter: &HashMap<u8, usize>) -> Vec<(u8, usize)> {
     frequency_list
 }
 
-#[derive(Debug, PartialEq)]
-struct HuffmanTreeNodeCharacter {
-    character: u8,
-    frequency: usize,
+/// Leaf of the tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HuffmanTreeNodeCharacter<S = u8> {
+    pub character: S,
+    pub frequency: usize,
 }
 
-#[derive(Debug, PartialEq)]
-struct HuffmanTreeNodeValue {
-    value: usize,
-    left: Option<Box<HuffmanTreeNode>>,
-    right: Option<Box<HuffmanTreeNode>>,
+/// Internal node of the tree; `value` is the sum of its children's values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HuffmanTreeNodeValue<S = u8> {
+    pub value: usize,
+    pub left: Option<Box<HuffmanTreeNode<S>>>,
+    pub right: Option<Box<HuffmanTreeNode<S>>>,
 }
 
-#[derive(Debug, PartialEq)]
-enum HuffmanTreeNode {
-    Character(HuffmanTreeNodeCharacter),
-    Value(HuffmanTreeNodeValue),
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HuffmanTreeNode<S = u8> {
+    Character(HuffmanTreeNodeCharacter<S>),
+    Value(HuffmanTreeNodeValue<S>),
 }
 
-impl HuffmanTreeNode {
-    fn value(&self) -> usize {
+impl<S> HuffmanTreeNode<S> {
+    /// Frequency of a leaf or combined frequency of an internal node.
+    pub fn value(&self) -> usize {
         match self {
             HuffmanTreeNode::Character(node) => node.frequency,
             HuffmanTreeNode::Value(node) => node.value,
         }
     }
+
+    /// Number of characters in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.nodes().filter(|(node, _)| node.is_leaf()).count()
+    }
+
+    /// Number of internal nodes, one less than `leaf_count` for trees built
+    /// by `huffman_tree`.
+    pub fn internal_count(&self) -> usize {
+        self.nodes().filter(|(node, _)| !node.is_leaf()).count()
+    }
+
+    /// Length of the longest path from the root to a leaf; 0 for a single
+    /// leaf.
+    pub fn depth(&self) -> usize {
+        self.nodes().map(|(_, depth)| depth).max().unwrap_or(0)
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(self, HuffmanTreeNode::Character(_))
+    }
+
+    // Every node with its depth, walked with an explicit stack so deep trees
+    // cannot overflow the call stack.
+    fn nodes(&self) -> impl Iterator<Item = (&HuffmanTreeNode<S>, usize)> {
+        let mut stack = vec![(self, 0)];
+        core::iter::from_fn(move || {
+            let (node, depth) = stack.pop()?;
+            if let HuffmanTreeNode::Value(node) = node {
+                for child in [&node.left, &node.right].into_iter().flatten() {
+                    stack.push((child, depth + 1));
+                }
+            }
+            Some((node, depth))
+        })
+    }
 }
 
-fn huffman_tree(frequency_list: &[(u8, usize)]) -> HuffmanTreeNode {
-    let character_node = HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
-        character: frequency_list[0].0,
-        frequency: frequency_list[0].1,
-    });
-    if frequency_list.len() == 1 {
-        return character_node;
+/// Draws the tree one node per line, internal nodes as `(value)` and leaves
+/// as `'character': frequency`, each child prefixed by its edge bit:
+///
+/// ```text
+/// (3)
+/// |-0- 'A': 2
+/// `-1- 'B': 1
+/// ```
+impl fmt::Display for HuffmanTreeNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, "")
     }
+}
 
-    let value_node = huffman_tree(&frequency_list[1..]);
+impl HuffmanTreeNode {
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: &str) -> fmt::Result {
+        match self {
+            HuffmanTreeNode::Character(node) => write!(
+                f,
+                "'{}': {}",
+                core::ascii::escape_default(node.character),
+                node.frequency
+            ),
+            HuffmanTreeNode::Value(node) => {
+                write!(f, "({})", node.value)?;
+                let children = [
+                    (&node.left, "|-0- ", "|    "),
+                    (&node.right, "`-1- ", "     "),
+                ];
+                for (child, edge, child_indent) in children {
+                    if let Some(child) = child {
+                        write!(f, "\n{indent}{edge}")?;
+                        child.fmt_indented(f, &format!("{indent}{child_indent}"))?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-    let (left, right) = if character_node.value() >= value_node.value() {
-        (character_node, value_node)
-    } else {
-        (value_node, character_node)
-    };
+impl<S: Symbol> HuffmanTreeNode<S> {
+    /// Code of `symbol` as in [`huffman_codes`], found by searching the tree
+    /// instead of building the whole table. `None` if the tree lacks it.
+    pub fn code_for(&self, symbol: S) -> Option<Vec<u8>> {
+        if let HuffmanTreeNode::Character(node) = self {
+            return (node.character == symbol).then(|| vec![b'1']);
+        }
+        let mut stack = vec![(self, Vec::new())];
+        while let Some((node, code)) = stack.pop() {
+            match node {
+                HuffmanTreeNode::Character(node) if node.character == symbol => {
+                    return Some(code);
+                }
+                HuffmanTreeNode::Character(_) => {}
+                HuffmanTreeNode::Value(node) => {
+                    for (child, bit) in [(&node.left, b'0'), (&node.right, b'1')] {
+                        if let Some(child) = child {
+                            let mut child_code = code.clone();
+                            child_code.push(bit);
+                            stack.push((child, child_code));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
 
-    HuffmanTreeNode::Value(HuffmanTreeNodeValue {
-        value: left.value() + right.value(),
-        left: Some(Box::new(left)),
-        right: Some(Box::new(right)),
-    })
+    /// Symbols sorted by code length and then by symbol, the order in which
+    /// [`canonical_codes`] hands out consecutive codes.
+    pub fn symbols_in_code_order(&self) -> Vec<S> {
+        let mut symbols = code_lengths(self)
+            .into_iter()
+            .map(|(c, length)| (length, c))
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols.into_iter().map(|(_, c)| c).collect()
+    }
+
+    fn min_character(&self) -> Option<S> {
+        self.nodes()
+            .filter_map(|(node, _)| match node {
+                HuffmanTreeNode::Character(node) => Some(node.character),
+                HuffmanTreeNode::Value(_) => None,
+            })
+            .min()
+    }
+}
+
+/// Orders nodes by value, then by the smallest character they contain, the
+/// order in which [`huffman_tree`] merges them. Nodes that are still equal are
+/// ordered by their shape, leaves first, so the order agrees with `==`.
+///
+/// Finding the smallest character walks the whole subtree, so wrap nodes
+/// with `Reverse` in a `BinaryHeap` sparingly on large trees.
+impl<S: Symbol> PartialOrd for HuffmanTreeNode<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: Symbol> Ord for HuffmanTreeNode<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value()
+            .cmp(&other.value())
+            .then_with(|| self.min_character().cmp(&other.min_character()))
+            .then_with(|| match (self, other) {
+                (HuffmanTreeNode::Character(a), HuffmanTreeNode::Character(b)) => {
+                    a.character.cmp(&b.character)
+                }
+                (HuffmanTreeNode::Character(_), HuffmanTreeNode::Value(_)) => Ordering::Less,
+                (HuffmanTreeNode::Value(_), HuffmanTreeNode::Character(_)) => Ordering::Greater,
+                (HuffmanTreeNode::Value(a), HuffmanTreeNode::Value(b)) => {
+                    a.left.cmp(&b.left).then_with(|| a.right.cmp(&b.right))
+                }
+            })
+    }
+}
+
+// Min-heap entry: lowest value first, ties broken by the smallest character
+// in the subtree. Characters are unique, so no two entries ever compare equal
+// and the resulting tree does not depend on the order nodes were pushed in.
+// Same order as the nodes' `Ord`, with the smallest character kept instead of
+// searched for on every comparison.
+struct HeapNode<S> {
+    min_character: S,
+    node: HuffmanTreeNode<S>,
+}
+
+impl<S: Symbol> PartialEq for HeapNode<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S: Symbol> Eq for HeapNode<S> {}
+
+impl<S: Symbol> PartialOrd for HeapNode<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: Symbol> Ord for HeapNode<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .node
+            .value()
+            .cmp(&self.node.value())
+            .then(other.min_character.cmp(&self.min_character))
+    }
+}
+
+/// Builds the Huffman tree for a list sorted as returned by [`frequency_list`].
+///
+/// The two lowest-value nodes are merged repeatedly, the larger one going on
+/// the left (the `0` edge). Equal values are merged in order of the smallest
+/// character each node contains, which goes on the left, so the tree only
+/// depends on the frequencies. Panics if `frequency_list` is empty, or if the
+/// frequencies add up to more than `usize::MAX`, which counts taken from data
+/// in memory never do.
+pub fn huffman_tree<S: Symbol>(frequency_list: &[(S, usize)]) -> HuffmanTreeNode<S> {
+    checked_huffman_tree(frequency_list).expect("Frequencies overflow usize")
+}
+
+fn checked_huffman_tree<S: Symbol>(
+    frequency_list: &[(S, usize)],
+) -> Result<HuffmanTreeNode<S>, EncodeError> {
+    let mut heap = frequency_list
+        .iter()
+        .map(|&(character, frequency)| HeapNode {
+            min_character: character,
+            node: HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+                character,
+                frequency,
+            }),
+        })
+        .collect::<BinaryHeap<_>>();
+    loop {
+        let HeapNode {
+            min_character,
+            node: first,
+        } = heap.pop().expect("Cannot build a tree without characters");
+        let Some(HeapNode {
+            min_character: second_min_character,
+            node: second,
+        }) = heap.pop()
+        else {
+            return Ok(first);
+        };
+
+        let value = first
+            .value()
+            .checked_add(second.value())
+            .ok_or(EncodeError::FrequencyOverflow)?;
+        let (left, right) = if second.value() > first.value() {
+            (second, first)
+        } else {
+            (first, second)
+        };
+
+        heap.push(HeapNode {
+            min_character: min_character.min(second_min_character),
+            node: HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+                value,
+                left: Some(Box::new(left)),
+                right: Some(Box::new(right)),
+            }),
+        });
+    }
+}
+
+/// Builds the tree from an external frequency model instead of the message
+/// being encoded. Characters with a weight of zero still get a code, so they
+/// can be encoded even if the model never saw them. Fails if the weights add
+/// up to more than `usize::MAX`.
+pub fn huffman_tree_from_weights<S: Symbol>(
+    weights: &Map<S, usize>,
+) -> Result<HuffmanTreeNode<S>, EncodeError> {
+    checked_huffman_tree(&frequency_list(weights))
+}
+
+/// Builds a tree over all 256 bytes from the counts of a sample, bytes it
+/// lacks counting as seen once, so data with any byte can be encoded and
+/// decoded with it. Panics like [`huffman_tree`] on overflow.
+pub fn huffman_tree_complete(counter: &Map<u8, usize>) -> HuffmanTreeNode {
+    let counts = (0..=255)
+        .map(|c| (c, counter.get(&c).copied().unwrap_or(0).max(1)))
+        .collect::<Map<_, _>>();
+    huffman_tree(&frequency_list(&counts))
+}
+
+/// Bytes that `counter` never saw, in increasing order, including those
+/// present with a count of 0: the ones [`huffman_tree_complete`] fills in.
+pub fn missing_symbols(counter: &Map<u8, usize>) -> Vec<u8> {
+    (0..=255)
+        .filter(|c| counter.get(c).copied().unwrap_or(0) == 0)
+        .collect()
 }
 
 // TODO(Otavio): Change this to be a more memory efficient data structure
 // like u8 -> (code: usize, length: u16)
 // or u8 -> (code: usize, length: u8) if code can be bigger than a byte
-type HuffmanCode = HashMap<u8, Vec<u8>>;
+pub type HuffmanCode<S = u8> = Map<S, Vec<u8>>;
 
-fn huffman_codes(tree: &HuffmanTreeNode) -> HuffmanCode {
+/// Maps each character of the tree to its code, written as ASCII `b'0'` and
+/// `b'1'` bytes. A tree with a single character gets the code `"1"`.
+///
+/// A table built once from a sample can be reused to encode other messages
+/// over the same alphabet:
+///
+/// ```
+/// use huffman_coding::{
+///     frequency_counter, frequency_list, huffman_codes, huffman_encode_with_codes, huffman_tree,
+/// };
+///
+/// let corpus = b"the quick brown fox jumps over the lazy dog";
+/// let tree = huffman_tree(&frequency_list(&frequency_counter(corpus)));
+/// let codes = huffman_codes(&tree);
+///
+/// for message in [&b"the fox"[..], b"lazy dog"] {
+///     let (encoded, _) = huffman_encode_with_codes(message, &codes).unwrap();
+///     assert!(encoded.len() < message.len());
+/// }
+/// ```
+pub fn huffman_codes<S: Symbol>(tree: &HuffmanTreeNode<S>) -> HuffmanCode<S> {
     let mut codes = HuffmanCode::new();
-    fn rec_huffman_codes(
-        branch: &Option<Box<HuffmanTreeNode>>,
-        code: &[u8],
-        codes: &mut HuffmanCode,
-    ) {
-        match branch {
-            Some(node_box) => match node_box.as_ref() {
-                HuffmanTreeNode::Character(node) => {
-                    codes.insert(node.character, code.to_vec());
+    if let HuffmanTreeNode::Character(node) = tree {
+        codes.insert(node.character, vec![b'1']);
+        return codes;
+    }
+    let mut stack = vec![(tree, Vec::new())];
+    while let Some((node, mut code)) = stack.pop() {
+        match node {
+            HuffmanTreeNode::Character(node) => {
+                codes.insert(node.character, code);
+            }
+            HuffmanTreeNode::Value(node) => {
+                if let Some(right) = &node.right {
+                    let mut right_code = code.clone();
+                    right_code.push(b'1');
+                    stack.push((right, right_code));
                 }
-                HuffmanTreeNode::Value(node) => {
-                    rec_huffman_codes(&node.left, &code.iter().chain(once(&b'0')).cloned().collect::<Vec<u8>>(), codes);
-                    rec_huffman_codes(&node.right, &code.iter().chain(once(&b'1')).cloned().collect::<Vec<u8>>(), codes);
+                if let Some(left) = &node.left {
+                    code.push(b'0');
+                    stack.push((left, code));
                 }
-            },
-            None => {}
+            }
         }
     }
-    match tree {
-        HuffmanTreeNode::Character(node) => {
-            codes.insert(node.character, vec![b'1']);
-        }
-        HuffmanTreeNode::Value(node) => {
-            rec_huffman_codes(&node.left, &[b'0'].to_vec(), &mut codes);
-            rec_huffman_codes(&node.right, &[b'1'].to_vec(), &mut codes);
+    codes
+}
+
+/// Checks that no code is a prefix of another, which a table must satisfy to
+/// be decoded unambiguously. Tables built by this crate always do; this is
+/// meant for tables written by hand or loaded from elsewhere.
+pub fn validate_prefix_free(codes: &HuffmanCode) -> Result<(), ValidationError> {
+    let mut codes = codes.iter().map(|(&c, code)| (code, c)).collect::<Vec<_>>();
+    codes.sort();
+    if let Some(&(_, c)) = codes.iter().find(|(code, _)| code.is_empty()) {
+        return Err(ValidationError::EmptyCode(c));
+    }
+    // In sorted order, a code that is a prefix of others comes right before
+    // the first of them.
+    match codes
+        .windows(2)
+        .find(|pair| pair[1].0.starts_with(pair[0].0))
+    {
+        Some(pair) => Err(ValidationError::NotPrefixFree(pair[0].1, pair[1].1)),
+        None => Ok(()),
+    }
+}
+
+/// Kraft sum `Σ 2^-length` of the codes: at most 1 for any prefix-free
+/// table, and exactly 1 when the table is complete, every bit sequence
+/// starting some code. A sum above 1 means no prefix-free table has these
+/// lengths; a sum within the bound does not prove this one is, which
+/// [`validate_prefix_free`] checks.
+pub fn kraft_sum(codes: &HuffmanCode) -> f64 {
+    codes
+        .values()
+        .map(|code| (0..code.len()).fold(1.0, |weight, _| weight / 2.0))
+        .sum()
+}
+
+/// The codes as strings such as `"010"`, for logging and debugging.
+pub fn codes_as_strings(codes: &HuffmanCode) -> Map<u8, String> {
+    codes
+        .iter()
+        .map(|(&c, code)| (c, code.iter().map(|&bit| bit as char).collect()))
+        .collect()
+}
+
+/// Same codes as [`HuffmanCode`], stored as `(code, length)` with the code
+/// right-aligned in the `u32`, so `[b'0', b'1', b'1']` becomes `(0b011, 3)`.
+/// It is looked up once per encoded character, so the `fast-hash` feature
+/// swaps SipHash for a cheaper hasher.
+#[cfg(not(feature = "fast-hash"))]
+type PackedHuffmanCode<S = u8> = Map<S, (u32, u8)>;
+#[cfg(feature = "fast-hash")]
+type PackedHuffmanCode<S = u8> = std::collections::HashMap<S, (u32, u8), fxhash::FxBuildHasher>;
+
+fn pack_code(bits: &[u8]) -> (u32, u8) {
+    assert!(bits.len() <= 32, "Code is too long to pack into a u32");
+    let code = bits
+        .iter()
+        .fold(0, |acc, &b| acc << 1 | if b == b'1' { 1 } else { 0 });
+    (code, bits.len() as u8)
+}
+
+fn packed_huffman_codes<S: Symbol>(codes: &HuffmanCode<S>) -> PackedHuffmanCode<S> {
+    codes
+        .iter()
+        .map(|(&c, code)| (c, pack_code(code)))
+        .collect()
+}
+
+/// Length of each character's code, i.e. the depth of its leaf. A tree with a
+/// single character reports a length of 1 to match [`huffman_codes`].
+pub fn code_lengths<S: Symbol>(tree: &HuffmanTreeNode<S>) -> Map<S, u8> {
+    let mut lengths = Map::new();
+    let mut stack = vec![(tree, 0u8)];
+    while let Some((node, depth)) = stack.pop() {
+        match node {
+            HuffmanTreeNode::Character(node) => {
+                lengths.insert(node.character, depth.max(1));
+            }
+            HuffmanTreeNode::Value(node) => {
+                for child in [&node.left, &node.right].into_iter().flatten() {
+                    stack.push((child, depth + 1));
+                }
+            }
         }
     }
+    lengths
+}
+
+/// [`code_lengths`] as an array indexed by byte, with 0 for bytes that have
+/// no code, the form DEFLATE-style formats store. The lengths are all
+/// [`canonical_codes`] needs.
+pub fn code_length_table(tree: &HuffmanTreeNode) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (c, length) in code_lengths(tree) {
+        table[c as usize] = length;
+    }
+    table
+}
+
+/// Length of the longest code of `tree`, e.g. to check it against the limit
+/// of a fixed-width format before falling back to [`length_limited_codes`].
+/// A single leaf has a 1-bit code.
+pub fn max_code_length<S: Symbol>(tree: &HuffmanTreeNode<S>) -> u8 {
+    code_lengths(tree).into_values().max().unwrap_or(0)
+}
+
+/// Canonical codes only depend on each character's code length: characters
+/// are sorted by (length, character) and given consecutive code values, so a
+/// decoder can rebuild the whole table from the lengths alone.
+pub fn canonical_codes<S: Symbol>(tree: &HuffmanTreeNode<S>) -> HuffmanCode<S> {
+    canonical_codes_from_lengths(&code_lengths(tree))
+}
+
+/// Assigns canonical codes, see [`canonical_codes`], to the given lengths.
+pub fn canonical_codes_from_lengths<S: Symbol>(lengths: &Map<S, u8>) -> HuffmanCode<S> {
+    let mut lengths = lengths
+        .iter()
+        .map(|(&c, &length)| (length as usize, c))
+        .collect::<Vec<_>>();
+    lengths.sort();
+
+    let mut codes = HuffmanCode::new();
+    let mut code = 0usize;
+    let mut previous_length = 0;
+    for (length, c) in lengths {
+        code <<= length - previous_length;
+        codes.insert(
+            c,
+            (0..length)
+                .rev()
+                .map(|i| if code >> i & 1 == 1 { b'1' } else { b'0' })
+                .collect(),
+        );
+        code += 1;
+        previous_length = length;
+    }
     codes
 }
 
-fn huffman_encode_string(text: &String) -> (Vec<u8>, HashMap<Vec<u8>, u8>) {
-    let frequency_counter = frequency_counter(text);
-    let frequency_list = frequency_list(&frequency_counter);
-    let tree = huffman_tree(&frequency_list);
-    let codes = huffman_codes(&tree);
-    let encoded = text
-        .as_bytes()
+/// Builds canonical codes no longer than `max_len` bits using the
+/// package-merge algorithm. The result is the cheapest prefix code under that
+/// limit, so it matches the plain Huffman lengths when they already fit.
+pub fn length_limited_codes<S: Symbol>(
+    counter: &Map<S, usize>,
+    max_len: u8,
+) -> Result<HuffmanCode<S>, EncodeError> {
+    let mut leaves = frequency_list(counter);
+    leaves.reverse();
+    let fits = (max_len as u32) >= usize::BITS || leaves.len() <= 1 << max_len;
+    if !leaves.is_empty() && (max_len == 0 || !fits) {
+        return Err(EncodeError::CodeLengthLimitTooSmall(max_len));
+    }
+    if leaves.len() == 1 {
+        return Ok(canonical_codes_from_lengths(
+            &[(leaves[0].0, 1)].into_iter().collect(),
+        ));
+    }
+
+    // Each item is a weight and the leaves it covers. Every round pairs up the
+    // cheapest items into packages and merges them back with the leaves.
+    let leaf_items = leaves
         .iter()
+        .enumerate()
+        .map(|(i, &(_, frequency))| (frequency as u128, vec![i]))
+        .collect::<Vec<_>>();
+    let mut items = leaf_items.clone();
+    for _ in 1..max_len {
+        let packages = items.chunks_exact(2).map(|pair| {
+            let leaves = pair[0].1.iter().chain(&pair[1].1).copied().collect();
+            (pair[0].0 + pair[1].0, leaves)
+        });
+        items = leaf_items.iter().cloned().chain(packages).collect();
+        items.sort_by_key(|(weight, _)| *weight);
+    }
+
+    let mut lengths = vec![0u8; leaves.len()];
+    for (_, covered) in items.iter().take(2 * leaves.len().saturating_sub(1)) {
+        for &i in covered {
+            lengths[i] += 1;
+        }
+    }
+    let lengths = leaves
+        .iter()
+        .zip(lengths)
+        .map(|(&(c, _), length)| (c, length))
+        .collect();
+    Ok(canonical_codes_from_lengths(&lengths))
+}
+
+fn encode_bits<S: Symbol>(data: &[S], codes: &HuffmanCode<S>) -> Vec<u8> {
+    data.iter()
         .flat_map(|c| {
             codes
                 .get(c)
@@ -122,23 +710,485 @@ fn huffman_encode_string(text: &String) -> (Vec<u8>, HashMap<Vec<u8>, u8>) {
                 .iter()
                 .map(|&c| if c == b'1' { 1 } else { 0 })
         })
-        .collect();
-    let decode_codes = codes
-        .into_iter()
-        .map(|(c, code)| (code, c))
-        .collect::<HashMap<_, _>>();
-    (encoded, decode_codes)
-}
-
-pub fn huffman_encode(text: &String) -> (Vec<u8>, HashMap<Vec<u8>, u8>) {
-    let (encoded, codes) = huffman_encode_string(text);
-    (
-        encoded
-            .chunks(8)
-            .map(|bytes| bytes.iter().fold(0, |acc, b| acc << 1 | b))
-            .collect::<Vec<u8>>(),
+        .collect()
+}
+
+fn decode_map<S: Symbol>(codes: HuffmanCode<S>) -> Map<Vec<u8>, S> {
+    codes.into_iter().map(|(c, code)| (code, c)).collect()
+}
+
+/// Encodes `data` into an unpacked bit vector holding one `0` or `1` per byte,
+/// along with the decode map. See [`huffman_decode_string`] for the inverse.
+pub fn huffman_encode_string<S: Symbol>(data: &[S]) -> (Vec<u8>, Map<Vec<u8>, S>) {
+    let frequency_counter = frequency_counter(data);
+    let frequency_list = frequency_list(&frequency_counter);
+    let tree = huffman_tree(&frequency_list);
+    let codes = huffman_codes(&tree);
+    (encode_bits(data, &codes), decode_map(codes))
+}
+
+/// Packs a bit-per-byte vector into bytes with a [`BitWriter`], so `[1, 0, 1]`
+/// becomes `0b10100000`.
+#[cfg(test)]
+fn pack_bits(bits: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::with_capacity(bits.len().div_ceil(8));
+    for &bit in bits {
+        writer.write_bit(bit == 1);
+    }
+    writer.finish().0
+}
+
+/// Encodes `data` into bytes filled most significant bit first, the last one
+/// zero-padded, and returns them with the bit length. Each code is written
+/// with a single [`BitWriter::write_bits`] call instead of going through a
+/// bit-per-byte vector. Fails with the first character of `data` that has no
+/// code.
+fn encode_packed<S: Symbol>(data: &[S], codes: &HuffmanCode<S>) -> Result<(Vec<u8>, usize), S> {
+    let mut writer = BitWriter::with_capacity(data.len() / 2);
+    write_codes(data, codes, &mut writer)?;
+    Ok(writer.finish())
+}
+
+/// [`encode_packed`] for bytes, looking codes up in a [`byte_code_table`]
+/// instead of hashing every byte.
+fn encode_packed_bytes(data: &[u8], codes: &HuffmanCode) -> Result<(Vec<u8>, usize), u8> {
+    let mut writer = BitWriter::with_capacity(data.len() / 2);
+    write_byte_codes(data, codes, &mut writer)?;
+    Ok(writer.finish())
+}
+
+/// Appends the codes of `data` to `acc` without padding, so several messages
+/// can share one bitstream that is only byte-aligned when `acc` is finished.
+/// On a byte without a code, the codes of the bytes before it have already
+/// been written.
+pub fn encode_into(
+    data: &[u8],
+    codes: &HuffmanCode,
+    acc: &mut BitWriter,
+) -> Result<(), EncodeError> {
+    write_byte_codes(data, codes, acc).map_err(EncodeError::NoCodeForSymbol)
+}
+
+/// Encodes `data` into `out` without allocating, for targets without a heap
+/// to spare, and returns the number of bytes written. The bits are packed
+/// and padded as by [`huffman_encode_with_codes`]. Fails with
+/// [`EncodeError::BufferTooSmall`] if they do not fit, leaving `out`
+/// partially written.
+pub fn encode_into_slice(
+    data: &[u8],
+    codes: &HuffmanCode,
+    out: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let mut writer = SliceWriter {
+        out,
+        len: 0,
+        acc: 0,
+        acc_len: 0,
+    };
+    match byte_code_table(codes) {
+        Some(table) => {
+            for &c in data {
+                let (code, length) = table[c as usize].ok_or(EncodeError::NoCodeForSymbol(c))?;
+                writer.write_bits(code, length)?;
+            }
+        }
+        None => {
+            for &c in data {
+                for &bit in codes.get(&c).ok_or(EncodeError::NoCodeForSymbol(c))? {
+                    writer.write_bits((bit == b'1') as u32, 1)?;
+                }
+            }
+        }
+    }
+    writer.finish()
+}
+
+// MSB-first bit packing into a borrowed buffer, for `encode_into_slice`.
+struct SliceWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+    // Bits not yet written, right-aligned; fewer than 8 between calls.
+    acc: u64,
+    acc_len: u8,
+}
+
+impl SliceWriter<'_> {
+    fn write_bits(&mut self, code: u32, length: u8) -> Result<(), EncodeError> {
+        self.acc = self.acc << length | code as u64;
+        self.acc_len += length;
+        while self.acc_len >= 8 {
+            self.acc_len -= 8;
+            self.push((self.acc >> self.acc_len) as u8)?;
+        }
+        self.acc &= (1 << self.acc_len) - 1;
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), EncodeError> {
+        *self
+            .out
+            .get_mut(self.len)
+            .ok_or(EncodeError::BufferTooSmall)? = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize, EncodeError> {
+        if self.acc_len > 0 {
+            self.push((self.acc << (8 - self.acc_len)) as u8)?;
+        }
+        Ok(self.len)
+    }
+}
+
+/// Packed code of each byte, indexed by the byte itself. `None` if a code is
+/// too long to pack.
+fn byte_code_table(codes: &HuffmanCode) -> Option<[Option<(u32, u8)>; 256]> {
+    let mut table = [None; 256];
+    for (&c, code) in codes {
+        if code.len() > 32 {
+            return None;
+        }
+        table[c as usize] = Some(pack_code(code));
+    }
+    Some(table)
+}
+
+fn write_byte_codes(data: &[u8], codes: &HuffmanCode, writer: &mut BitWriter) -> Result<(), u8> {
+    let Some(table) = byte_code_table(codes) else {
+        return write_codes(data, codes, writer);
+    };
+    for &c in data {
+        let (code, length) = table[c as usize].ok_or(c)?;
+        writer.write_bits(code as u64, length);
+    }
+    Ok(())
+}
+
+fn write_codes<S: Symbol>(
+    data: &[S],
+    codes: &HuffmanCode<S>,
+    writer: &mut BitWriter,
+) -> Result<(), S> {
+    // Only pathological frequencies over millions of characters produce codes
+    // that do not fit in a u32; those are written bit by bit.
+    if codes.values().any(|code| code.len() > 32) {
+        for c in data {
+            for &bit in codes.get(c).ok_or(*c)? {
+                writer.write_bit(bit == b'1');
+            }
+        }
+    } else {
+        let codes = packed_huffman_codes(codes);
+        for c in data {
+            let &(code, length) = codes.get(c).ok_or(*c)?;
+            writer.write_bits(code as u64, length);
+        }
+    }
+    Ok(())
+}
+
+/// Yields every bit of `bytes` as a `0` or `1`, including padding.
+fn unpack_bits(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    BitReader::new(bytes).map(u8::from)
+}
+
+/// Encodes `data`, returning the packed bytes, the decode map and the number
+/// of meaningful bits. Bits are packed MSB-first and the last byte is padded
+/// with zero bits.
+///
+/// When `data` holds a single distinct character its code is `"1"`, so every
+/// occurrence costs exactly one bit and the decoder emits that character for
+/// each bit it consumes.
+pub fn huffman_encode_symbols<S: Symbol>(data: &[S]) -> (Vec<u8>, Map<Vec<u8>, S>, usize) {
+    let tree = huffman_tree(&frequency_list(&frequency_counter(data)));
+    let codes = huffman_codes(&tree);
+    let Ok((encoded, bit_len)) = encode_packed(data, &codes) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    (encoded, decode_map(codes), bit_len)
+}
+
+/// Byte version of [`huffman_encode_symbols`].
+pub fn huffman_encode_bytes(data: &[u8]) -> (Vec<u8>, Map<Vec<u8>, u8>, usize) {
+    let tree = huffman_tree(&byte_frequency_list(data));
+    let codes = huffman_codes(&tree);
+    let Ok((encoded, bit_len)) = encode_packed_bytes(data, &codes) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    (encoded, decode_map(codes), bit_len)
+}
+
+/// Same encoding as [`huffman_encode_bytes`], returning the tree instead of
+/// the decode map so decoders can walk it directly.
+pub fn huffman_encode_with_tree(data: &[u8]) -> (Vec<u8>, HuffmanTreeNode, usize) {
+    let tree = huffman_tree(&byte_frequency_list(data));
+    let Ok((encoded, bit_len)) = encode_packed_bytes(data, &huffman_codes(&tree)) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    (encoded, tree, bit_len)
+}
+
+/// Both forms of a message's encoding, as returned by
+/// [`huffman_encode_full`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeResult {
+    /// One `0` or `1` per bit, as from [`huffman_encode_string`].
+    pub bits: Vec<u8>,
+    /// The bits packed into bytes, as from [`huffman_encode_bytes`].
+    pub packed: Vec<u8>,
+    /// Number of meaningful bits in `packed`.
+    pub bit_len: usize,
+    /// The code of each byte.
+    pub codes: HuffmanCode,
+}
+
+/// Encodes `data` into both the unpacked bit vector and the packed bytes,
+/// building the codes once.
+pub fn huffman_encode_full(data: &[u8]) -> EncodeResult {
+    let codes = huffman_codes(&huffman_tree(&byte_frequency_list(data)));
+    let Ok((packed, bit_len)) = encode_packed_bytes(data, &codes) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    EncodeResult {
+        bits: encode_bits(data, &codes),
+        packed,
+        bit_len,
         codes,
-    )
+    }
+}
+
+/// Everything built from a message to encode and decode it: the tree, the
+/// codes and their inverse, as returned by [`huffman_build`].
+#[derive(Debug, Clone)]
+pub struct HuffmanModel {
+    tree: HuffmanTreeNode,
+    codes: HuffmanCode,
+    inverse_codes: Map<Vec<u8>, u8>,
+}
+
+/// Builds the tree and both code maps for `data` once, for callers that need
+/// more than one of them. Panics if `data` is empty.
+pub fn huffman_build(data: &[u8]) -> HuffmanModel {
+    let tree = huffman_tree(&byte_frequency_list(data));
+    let codes = huffman_codes(&tree);
+    let inverse_codes = decode_map(codes.clone());
+    HuffmanModel {
+        tree,
+        codes,
+        inverse_codes,
+    }
+}
+
+impl HuffmanModel {
+    pub fn tree(&self) -> &HuffmanTreeNode {
+        &self.tree
+    }
+
+    pub fn codes(&self) -> &HuffmanCode {
+        &self.codes
+    }
+
+    /// The decode map, as returned by [`huffman_encode_bytes`].
+    pub fn inverse_codes(&self) -> &Map<Vec<u8>, u8> {
+        &self.inverse_codes
+    }
+
+    /// Encodes `data` like [`huffman_encode_with_codes`]. Fails if `data` has a
+    /// byte the model was not built with.
+    pub fn encode(&self, data: &[u8]) -> Result<(Vec<u8>, usize), EncodeError> {
+        huffman_encode_with_codes(data, &self.codes)
+    }
+
+    /// Decodes `original_len` bytes encoded with this model.
+    pub fn decode(&self, encoded: &[u8], original_len: usize) -> Result<Vec<u8>, DecodeError> {
+        decode_with_tree(encoded, &self.tree, original_len).map(|(decoded, _)| decoded)
+    }
+}
+
+/// Encodes `data` with a prebuilt code table, returning the packed bytes and
+/// the number of meaningful bits.
+pub fn huffman_encode_with_codes(
+    data: &[u8],
+    codes: &HuffmanCode,
+) -> Result<(Vec<u8>, usize), EncodeError> {
+    encode_packed_bytes(data, codes).map_err(EncodeError::NoCodeForSymbol)
+}
+
+/// Joins two `(bytes, bit_len)` bitstreams, such as two outputs of
+/// [`huffman_encode_with_codes`] with the same table, at the bit level: the
+/// padding after `a` is dropped and `b` is shifted up against it, so the
+/// result decodes as `a`'s message followed by `b`'s. Panics if a bit length
+/// exceeds its bytes.
+pub fn concat_encoded(a: (&[u8], usize), b: (&[u8], usize)) -> (Vec<u8>, usize) {
+    let mut writer = BitWriter::with_capacity((a.1 + b.1).div_ceil(8));
+    for (bytes, bit_len) in [a, b] {
+        assert!(bit_len <= 8 * bytes.len(), "Bit length exceeds the bytes");
+        let (full, rest) = bytes[..bit_len.div_ceil(8)].split_at(bit_len / 8);
+        for &byte in full {
+            writer.write_bits(byte as u64, 8);
+        }
+        if let Some(&last) = rest.first() {
+            let count = (bit_len % 8) as u8;
+            writer.write_bits((last >> (8 - count)) as u64, count);
+        }
+    }
+    writer.finish()
+}
+
+/// Encodes the UTF-8 bytes of `text`, so a character outside ASCII is coded
+/// as the 2 to 4 bytes it takes, each a symbol of its own. Use
+/// [`huffman_encode_chars`] to code whole characters instead.
+pub fn huffman_encode(text: &String) -> (Vec<u8>, Map<Vec<u8>, u8>, usize) {
+    huffman_encode_bytes(text.as_bytes())
+}
+
+/// Encodes `text` with one symbol per `char`, so accented letters and emoji
+/// get a single code each rather than one per UTF-8 byte. Decode with
+/// [`huffman_decode_symbols`] and the number of `char`s.
+pub fn huffman_encode_chars(text: &str) -> (Vec<u8>, Map<Vec<u8>, char>, usize) {
+    huffman_encode_symbols(&text.chars().collect::<Vec<_>>())
+}
+
+/// Encodes the bytes yielded by `iter`, e.g. a generator or an adapter over
+/// some other source, returning the codes rather than the decode map. The
+/// bytes are collected once, since the codes depend on all of them before
+/// the first can be written. Panics if `iter` yields nothing.
+pub fn huffman_encode_iter<I: IntoIterator<Item = u8>>(iter: I) -> (Vec<u8>, HuffmanCode, usize) {
+    let data = iter.into_iter().collect::<Vec<_>>();
+    let codes = huffman_codes(&huffman_tree(&byte_frequency_list(&data)));
+    let Ok((encoded, bit_len)) = encode_packed_bytes(&data, &codes) else {
+        unreachable!("Codes are built from the data, so every character has one");
+    };
+    (encoded, codes, bit_len)
+}
+
+/// Serializes the tree shape in pre-order: a `0` bit for each internal node
+/// and a `1` bit followed by `symbol_bits` character bits for each leaf,
+/// packed by a [`BitWriter`], most significant bit first with the last byte
+/// zero-padded. Frequencies are not stored.
+fn serialize_tree<S: Symbol + Into<u64>>(tree: &HuffmanTreeNode<S>, symbol_bits: u8) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // Right children are pushed first so left subtrees are written first.
+    let mut stack = vec![tree];
+    while let Some(node) = stack.pop() {
+        match node {
+            HuffmanTreeNode::Character(node) => {
+                writer.write_bit(true);
+                writer.write_bits(node.character.into(), symbol_bits);
+            }
+            HuffmanTreeNode::Value(node) => {
+                writer.write_bit(false);
+                stack.extend(
+                    [&node.right, &node.left]
+                        .into_iter()
+                        .flatten()
+                        .map(|c| &**c),
+                );
+            }
+        }
+    }
+    writer.finish().0
+}
+
+/// Rebuilds a tree written by [`serialize_tree`]. Every frequency and value in
+/// the returned tree is zero since the serialized form only keeps the shape.
+fn deserialize_tree<S: Symbol + TryFrom<u64>>(
+    bytes: &[u8],
+    symbol_bits: u8,
+) -> Result<HuffmanTreeNode<S>, DecodeError> {
+    fn rec_deserialize_tree<S: Symbol + TryFrom<u64>>(
+        reader: &mut BitReader,
+        symbol_bits: u8,
+        depth: usize,
+    ) -> Result<HuffmanTreeNode<S>, DecodeError> {
+        // A tree over 2^n characters is at most 2^n - 1 levels deep.
+        if depth >= 1 << symbol_bits {
+            return Err(DecodeError::BadHeader);
+        }
+        if reader.read_bit().ok_or(DecodeError::TruncatedTree)? {
+            let character = reader
+                .read_bits(symbol_bits)
+                .ok_or(DecodeError::TruncatedTree)?;
+            Ok(HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+                character: S::try_from(character).map_err(|_| DecodeError::BadHeader)?,
+                frequency: 0,
+            }))
+        } else {
+            Ok(HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+                value: 0,
+                left: Some(Box::new(rec_deserialize_tree(
+                    reader,
+                    symbol_bits,
+                    depth + 1,
+                )?)),
+                right: Some(Box::new(rec_deserialize_tree(
+                    reader,
+                    symbol_bits,
+                    depth + 1,
+                )?)),
+            }))
+        }
+    }
+    rec_deserialize_tree(&mut BitReader::new(bytes), symbol_bits, 0)
+}
+
+/// Serializes code lengths for the 256 bytes as a sequence of 4-bit tokens:
+/// `1..=15` is the length of the next byte's code, and `0` followed by 8 bits
+/// `n` means the next `n + 1` bytes have no code. Bytes after the last token
+/// have no code either. Lengths must be at most 15.
+fn serialize_code_lengths(lengths: &Map<u8, u8>) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let mut missing = 0;
+    for c in 0..=255 {
+        match lengths.get(&c) {
+            Some(&length) => {
+                assert!((1..=15).contains(&length), "Code length must be 1 to 15");
+                if missing > 0 {
+                    writer.write_bits(0, 4);
+                    writer.write_bits(missing - 1, 8);
+                    missing = 0;
+                }
+                writer.write_bits(length as u64, 4);
+            }
+            None => missing += 1,
+        }
+    }
+    writer.finish().0
+}
+
+/// Reads lengths written by [`serialize_code_lengths`]. A trailing `0` token
+/// without its count is the padding of the last byte. Lengths that cannot
+/// form a prefix code are rejected.
+fn deserialize_code_lengths(bytes: &[u8]) -> Result<Map<u8, u8>, DecodeError> {
+    let mut reader = BitReader::new(bytes);
+    let mut lengths = Map::new();
+    let mut c = 0;
+    while let Some(token) = reader.read_bits(4) {
+        if token == 0 {
+            let Some(missing) = reader.read_bits(8) else {
+                break;
+            };
+            c += missing as usize + 1;
+        } else {
+            let character = u8::try_from(c).map_err(|_| DecodeError::BadHeader)?;
+            lengths.insert(character, token as u8);
+            c += 1;
+        }
+        if c > 256 {
+            return Err(DecodeError::BadHeader);
+        }
+    }
+
+    // Kraft's inequality: the lengths fit in a binary tree.
+    let kraft = lengths
+        .values()
+        .map(|&length| 1u32 << (15 - length))
+        .sum::<u32>();
+    if lengths.is_empty() || kraft > 1 << 15 {
+        return Err(DecodeError::BadHeader);
+    }
+    Ok(lengths)
 }
 
 // This is how to decode properly:
@@ -163,35 +1213,809 @@ pub fn huffman_encode(text: &String) -> (Vec<u8>, HashMap<Vec<u8>, u8>) {
 
 // The important point is every time we find a character, we take the next bit from the encoded string and start at the root of the tree.
 
+/// Rebuilds a tree from a code table, so a decoder can follow one edge per
+/// bit instead of looking up every prefix. Nodes of the result have a value
+/// of 0, and branches no code goes through are `None`.
+pub fn build_decode_tree<S: Symbol>(codes: &HuffmanCode<S>) -> HuffmanTreeNode<S> {
+    decode_tree(codes.iter().map(|(&c, code)| (code.as_slice(), c)))
+}
+
+// Tree to walk with `decode_step`. Going through the codes gives a single-leaf
+// tree its `1` edge, so it decodes one character per `1` bit; other trees are
+// walked as they are.
+fn decode_tree_for<S: Symbol>(tree: &HuffmanTreeNode<S>) -> Cow<'_, HuffmanTreeNode<S>> {
+    match tree {
+        HuffmanTreeNode::Character(_) => Cow::Owned(build_decode_tree(&huffman_codes(tree))),
+        tree => Cow::Borrowed(tree),
+    }
+}
+
+// Codes are inserted shortest first. One that runs into an existing leaf could
+// never be matched by a decoder and is left out.
+fn decode_tree<'a, S: Symbol>(codes: impl Iterator<Item = (&'a [u8], S)>) -> HuffmanTreeNode<S> {
+    let mut codes = codes.collect::<Vec<_>>();
+    codes.sort_by(|(code_1, c_1), (code_2, c_2)| {
+        code_1
+            .len()
+            .cmp(&code_2.len())
+            .then(code_1.cmp(code_2))
+            .then(c_1.cmp(c_2))
+    });
+    let empty = || {
+        HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+            value: 0,
+            left: None,
+            right: None,
+        })
+    };
+    let mut root = empty();
+    for (code, character) in codes {
+        let Some((&last, path)) = code.split_last() else {
+            continue;
+        };
+        let mut node = &mut root;
+        for &bit in path {
+            let HuffmanTreeNode::Value(inner) = node else {
+                break;
+            };
+            let child = if bit == b'1' {
+                &mut inner.right
+            } else {
+                &mut inner.left
+            };
+            node = child.get_or_insert_with(|| Box::new(empty()));
+        }
+        if let HuffmanTreeNode::Value(inner) = node {
+            let child = if last == b'1' {
+                &mut inner.right
+            } else {
+                &mut inner.left
+            };
+            child.get_or_insert_with(|| {
+                Box::new(HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+                    character,
+                    frequency: 0,
+                }))
+            });
+        }
+    }
+    root
+}
+
+// Follows the edge for `bit` from `node`. On reaching a leaf its character is
+// returned and `node` goes back to the root, `tree`.
+fn decode_step<'a, S: Symbol>(
+    tree: &'a HuffmanTreeNode<S>,
+    node: &mut &'a HuffmanTreeNode<S>,
+    bit: bool,
+) -> Result<Option<S>, DecodeError> {
+    let HuffmanTreeNode::Value(inner) = *node else {
+        unreachable!("Decoding always restarts from an internal node");
+    };
+    let child = if bit { &inner.right } else { &inner.left };
+    match child.as_deref() {
+        Some(HuffmanTreeNode::Character(leaf)) => {
+            *node = tree;
+            Ok(Some(leaf.character))
+        }
+        Some(next) => {
+            *node = next;
+            Ok(None)
+        }
+        None => Err(DecodeError::InvalidCode),
+    }
+}
+
+/// Decodes `bits` by walking `tree` from the root, one edge per bit. With
+/// `len` set, decoding stops after that many characters and any remaining
+/// bits are treated as padding; otherwise every bit must belong to a complete
+/// code.
+fn decode_characters<S: Symbol>(
+    bits: impl Iterator<Item = u8>,
+    tree: &HuffmanTreeNode<S>,
+    len: Option<usize>,
+) -> Result<Vec<S>, DecodeError> {
+    // Every character takes at least one bit, so a length read from corrupt
+    // data cannot reserve more than the bits could hold.
+    let max_len = bits.size_hint().1.unwrap_or(0);
+    let mut decoded = Vec::with_capacity(len.unwrap_or(0).min(max_len));
+    decode_characters_into(bits, tree, len, &mut decoded)?;
+    Ok(decoded)
+}
+
+// `decode_characters` appending to `out`.
+fn decode_characters_into<S: Symbol>(
+    bits: impl Iterator<Item = u8>,
+    tree: &HuffmanTreeNode<S>,
+    len: Option<usize>,
+    out: &mut Vec<S>,
+) -> Result<(), DecodeError> {
+    if len == Some(0) {
+        return Ok(());
+    }
+    let end = len.map(|len| out.len().saturating_add(len));
+    let mut node = tree;
+    // Stops right after the last character so no bit past it is read.
+    for bit in bits {
+        if let Some(c) = decode_step(tree, &mut node, bit == 1)? {
+            out.push(c);
+            if Some(out.len()) == end {
+                break;
+            }
+        }
+    }
+    match end {
+        Some(end) if out.len() < end => Err(DecodeError::UnexpectedEof),
+        None if !core::ptr::eq(node, tree) => Err(DecodeError::UnexpectedEof),
+        _ => Ok(()),
+    }
+}
+
+// Tree for decoding with a map returned by the encoders.
+fn decode_map_tree<S: Symbol>(codes: &Map<Vec<u8>, S>) -> HuffmanTreeNode<S> {
+    decode_tree(codes.iter().map(|(code, &c)| (code.as_slice(), c)))
+}
+
+/// Decodes `count` characters from any source of bits by walking `tree` as
+/// described above, without reading past the last one. A tree that is a
+/// single leaf decodes one character per `1` bit, matching its `"1"` code.
+pub fn decode_bits<I: Iterator<Item = bool>>(
+    bits: I,
+    tree: &HuffmanTreeNode,
+    count: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    decode_characters(bits.map(u8::from), &decode_tree_for(tree), Some(count))
+}
+
+/// Like [`decode_bits`] on packed `bits`, appending the characters to `out`
+/// so one buffer can be reused across many messages. On error, the characters
+/// decoded before it are left in `out`.
+pub fn decode_into(
+    bits: &[u8],
+    tree: &HuffmanTreeNode,
+    count: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), DecodeError> {
+    // Each character takes at least one bit, which bounds a corrupt count.
+    out.reserve(count.min(bits.len() * 8));
+    decode_characters_into(unpack_bits(bits), &decode_tree_for(tree), Some(count), out)
+}
+
+/// Decodes `symbol_count` characters from packed `bits` with [`decode_bits`],
+/// so trailing padding bits are ignored.
+///
+/// Also returns how many bytes of `bits` hold the decoded characters, so a
+/// stream embedded in a larger buffer can be followed by other data.
+pub fn decode_with_tree(
+    bits: &[u8],
+    tree: &HuffmanTreeNode,
+    symbol_count: usize,
+) -> Result<(Vec<u8>, usize), DecodeError> {
+    let mut bits_read = 0usize;
+    let counted_bits = BitReader::new(bits).inspect(|_| bits_read += 1);
+    let decoded = decode_bits(counted_bits, tree, symbol_count)?;
+    Ok((decoded, bits_read.div_ceil(8)))
+}
+
+/// Lazily decodes packed bits by walking a tree, see [`decode_iter`].
+#[derive(Debug, Clone)]
+pub struct DecodeIter<'a> {
+    bits: BitReader<'a>,
+    tree: &'a HuffmanTreeNode,
+}
+
+/// Iterates over the characters decoded from packed `bits`, like
+/// [`decode_with_tree`] without collecting them. Padding bits are decoded as
+/// well, so limit the iterator with `take` to the number of characters
+/// encoded. Iteration ends when the bits run out in the middle of a code or
+/// do not match any code.
+pub fn decode_iter<'a>(bits: &'a [u8], tree: &'a HuffmanTreeNode) -> DecodeIter<'a> {
+    DecodeIter {
+        bits: BitReader::new(bits),
+        tree,
+    }
+}
+
+impl Iterator for DecodeIter<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let mut node = self.tree;
+        if let HuffmanTreeNode::Character(leaf) = node {
+            return self.bits.read_bit()?.then_some(leaf.character);
+        }
+        loop {
+            let HuffmanTreeNode::Value(inner) = node else {
+                unreachable!("Decoding always restarts from an internal node");
+            };
+            let child = if self.bits.read_bit()? {
+                &inner.right
+            } else {
+                &inner.left
+            };
+            match child.as_deref()? {
+                HuffmanTreeNode::Character(leaf) => return Some(leaf.character),
+                next => node = next,
+            }
+        }
+    }
+}
+
+pub fn huffman_decode_string(bits: &[u8], codes: &Map<Vec<u8>, u8>) -> Result<String, DecodeError> {
+    let decoded = decode_characters(bits.iter().copied(), &decode_map_tree(codes), None)?;
+    String::from_utf8(decoded).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Decodes `original_len` characters encoded by [`huffman_encode_symbols`].
+pub fn huffman_decode_symbols<S: Symbol>(
+    encoded: &[u8],
+    codes: &Map<Vec<u8>, S>,
+    original_len: usize,
+) -> Result<Vec<S>, DecodeError> {
+    decode_characters(
+        unpack_bits(encoded),
+        &decode_map_tree(codes),
+        Some(original_len),
+    )
+}
+
+/// Byte version of [`huffman_decode_symbols`].
+pub fn huffman_decode_bytes(
+    encoded: &[u8],
+    codes: &Map<Vec<u8>, u8>,
+    original_len: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    huffman_decode_symbols(encoded, codes, original_len)
+}
+
+pub fn huffman_decode(
+    encoded: &[u8],
+    codes: &Map<Vec<u8>, u8>,
+    original_len: usize,
+) -> Result<String, DecodeError> {
+    let decoded = huffman_decode_bytes(encoded, codes, original_len)?;
+    String::from_utf8(decoded).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Encodes `data` with codes built from it, decodes the result and returns
+/// whether it matches `data`. Meant as a self-test for users' test suites.
+pub fn verify_round_trip(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return true;
+    }
+    verify_round_trip_with_codes(
+        data,
+        &huffman_codes(&huffman_tree(&byte_frequency_list(data))),
+    )
+}
+
+/// Like [`verify_round_trip`] with a custom table. False if a byte of `data`
+/// has no code, or if the table is ambiguous so that decoding gives back
+/// something else.
+pub fn verify_round_trip_with_codes(data: &[u8], codes: &HuffmanCode) -> bool {
+    let Ok((encoded, _)) = huffman_encode_with_codes(data, codes) else {
+        return false;
+    };
+    decode_bits(
+        BitReader::new(&encoded),
+        &build_decode_tree(codes),
+        data.len(),
+    )
+    .is_ok_and(|decoded| decoded == data)
+}
+
 #[cfg(test)]
 mod tests {
-    // use rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
+    use rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use alloc::string::ToString;
+
+    // #[test]
+    // fn really_big_string_encode() {
+    //     let r = StdRng::seed_from_u64(42);
+    //     let text: String = r
+    //         .sample_iter(&Alphanumeric)
+    //         .take(1024 * 20)
+    //         .map(char::from)
+    //         .collect();
+    //     let (encoded, _) = huffman_encode_string(text.as_bytes());
+    // }
+
+    #[test]
+    fn test_huffman_decode() {
+        let text = String::from("AABCBAD");
+        let (encoded, decode_codes, _) = huffman_encode(&text);
+        let result = huffman_decode(&encoded, &decode_codes, text.len()).unwrap();
+        assert_eq!(result, text);
+    }
+
+    #[test]
+    fn test_decode_with_tree() {
+        // The worked example in the comments above `build_decode_tree`.
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        let encoded = [0b11000100, 0b01011000];
+        assert_eq!(
+            decode_with_tree(&encoded, &tree, 7).unwrap(),
+            (b"AABCBAD".to_vec(), 2)
+        );
+        assert_eq!(
+            decode_with_tree(&encoded, &tree, 3).unwrap(),
+            (b"AAB".to_vec(), 1)
+        );
+        assert!(matches!(
+            decode_with_tree(&encoded, &tree, 20),
+            Err(DecodeError::UnexpectedEof)
+        ));
+
+        let (encoded, tree, _) = huffman_encode_with_tree(b"AAAA");
+        assert_eq!(
+            decode_with_tree(&encoded, &tree, 4).unwrap(),
+            (b"AAAA".to_vec(), 1)
+        );
+    }
+
+    #[test]
+    fn test_decode_bits() {
+        let text = b"this is an example of a huffman tree";
+        let (bits, codes) = huffman_encode_string(text);
+        let tree = decode_map_tree(&codes);
+        let decoded = decode_bits(bits.iter().map(|&bit| bit == 1), &tree, text.len()).unwrap();
+        assert_eq!(decoded, text);
+
+        let bits = [true, false, false, true, true];
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        assert_eq!(decode_bits(bits.into_iter(), &tree, 3).unwrap(), b"ABA");
+        assert!(matches!(
+            decode_bits(bits.into_iter(), &tree, 5),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_huffman_build() {
+        let text = String::from("this is an example of a huffman tree");
+        let model = huffman_build(text.as_bytes());
+        let (encoded, decode_codes, bit_len) = huffman_encode(&text);
+        assert_eq!(
+            model.encode(text.as_bytes()).unwrap(),
+            (encoded.clone(), bit_len)
+        );
+        assert_eq!(model.inverse_codes(), &decode_codes);
+        assert_eq!(model.codes(), &huffman_codes(model.tree()));
+        assert_eq!(model.decode(&encoded, text.len()).unwrap(), text.as_bytes());
+        assert!(matches!(
+            model.encode(b"zzz"),
+            Err(EncodeError::NoCodeForSymbol(b'z'))
+        ));
+    }
+
+    #[test]
+    fn test_concat_encoded() {
+        let codes = huffman_codes(&huffman_tree(&frequency_list(&frequency_counter(
+            b"this is an example of a huffman tree",
+        ))));
+        let tree = build_decode_tree(&codes);
+        for (first, second) in [
+            (&b"this is"[..], &b" a tree"[..]),
+            (b"", b"a huffman"),
+            (b"example", b""),
+            (b"tree", b"s"),
+        ] {
+            let a = huffman_encode_with_codes(first, &codes).unwrap();
+            let b = huffman_encode_with_codes(second, &codes).unwrap();
+            let (joined, bit_len) = concat_encoded((&a.0, a.1), (&b.0, b.1));
+            assert_eq!(bit_len, a.1 + b.1);
+            assert_eq!(joined.len(), bit_len.div_ceil(8));
+            let message = [first, second].concat();
+            assert_eq!(
+                decode_with_tree(&joined, &tree, message.len()).unwrap().0,
+                message
+            );
+        }
+
+        // Bits past the length, in `a` or `b`, do not leak into the result.
+        assert_eq!(
+            concat_encoded((&[0b1011_1111], 3), (&[0b0111_1111], 2)),
+            (vec![0b1010_1000], 5)
+        );
+    }
+
+    #[test]
+    fn test_encode_into() {
+        // A=1, B=00, C=010, D=011.
+        let codes = huffman_codes(&huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]));
+        let messages: [&[u8]; 2] = [b"AAB", b"ABA"];
+        let mut acc = BitWriter::new();
+        for message in messages {
+            encode_into(message, &codes, &mut acc).unwrap();
+        }
+        let (shared, bit_len) = acc.finish();
+        assert_eq!(shared, [0b1100_1001]);
+        assert_eq!(bit_len, 8);
+
+        let separate = messages
+            .iter()
+            .map(|message| huffman_encode_with_codes(message, &codes).unwrap().0.len())
+            .sum::<usize>();
+        assert!(shared.len() < separate);
+
+        let tree = build_decode_tree(&codes);
+        let mut bits = BitReader::new(&shared);
+        for message in messages {
+            assert_eq!(
+                decode_bits(bits.by_ref(), &tree, message.len()).unwrap(),
+                message
+            );
+        }
+
+        let mut acc = BitWriter::new();
+        assert!(matches!(
+            encode_into(b"AZ", &codes, &mut acc),
+            Err(EncodeError::NoCodeForSymbol(b'Z'))
+        ));
+        assert_eq!(acc.bit_len(), 1);
+    }
+
+    #[test]
+    fn test_encode_into_slice() {
+        let codes_for =
+            |data: &[u8]| huffman_codes(&huffman_tree(&frequency_list(&frequency_counter(data))));
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..1000).map(|_| r.gen_range(0..16)).collect::<Vec<u8>>();
+        // Codes too long to pack are written bit by bit.
+        let long = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', vec![b'1'; 40])]);
+        let cases = [
+            (&b"AABCBAD"[..], codes_for(b"AABCBAD")),
+            (b"", codes_for(b"AABCBAD")),
+            (&random, codes_for(&random)),
+            (b"ABAB", long),
+        ];
+        for (data, codes) in cases {
+            let (expected, _) = huffman_encode_with_codes(data, &codes).unwrap();
+            let mut out = [0; 1024];
+            let written = encode_into_slice(data, &codes, &mut out).unwrap();
+            assert_eq!(&out[..written], expected);
+            // An exact fit is enough.
+            let mut exact = vec![0; expected.len()];
+            assert_eq!(
+                encode_into_slice(data, &codes, &mut exact).unwrap(),
+                written
+            );
+        }
+
+        let codes = codes_for(b"AABCBAD");
+        let mut out = [0; 1];
+        assert!(matches!(
+            encode_into_slice(b"AABCBAD", &codes, &mut out),
+            Err(EncodeError::BufferTooSmall)
+        ));
+        assert!(matches!(
+            encode_into_slice(b"AZ", &codes, &mut out),
+            Err(EncodeError::NoCodeForSymbol(b'Z'))
+        ));
+    }
+
+    #[test]
+    fn test_verify_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..1000).map(|_| r.gen::<u8>()).collect::<Vec<_>>();
+        for data in [&b""[..], b"A", b"AABCBAD", &random] {
+            assert!(verify_round_trip(data));
+        }
+
+        let mut codes = huffman_codes(&huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]));
+        assert!(verify_round_trip_with_codes(b"AABCBAD", &codes));
+        // B and C now share a code.
+        codes.insert(b'C', codes[&b'B'].clone());
+        assert!(!verify_round_trip_with_codes(b"AABCBAD", &codes));
+        codes.remove(&b'D');
+        assert!(!verify_round_trip_with_codes(b"AABCBAD", &codes));
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        let messages: [&[u8]; 3] = [b"AABCBAD", b"DCBA", b"B"];
+        let mut out = Vec::with_capacity(16);
+        let capacity = out.capacity();
+        for message in messages {
+            let (encoded, _) = huffman_encode_with_codes(message, &huffman_codes(&tree)).unwrap();
+            out.clear();
+            decode_into(&encoded, &tree, message.len(), &mut out).unwrap();
+            assert_eq!(out, message);
+            assert_eq!(out.capacity(), capacity);
+        }
+
+        let mut out = b"AB".to_vec();
+        decode_into(&[0b11000100, 0b01011000], &tree, 3, &mut out).unwrap();
+        assert_eq!(out, b"ABAAB");
+        assert!(matches!(
+            decode_into(&[0b11000100], &tree, 7, &mut out),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        // A count far beyond the bits fails instead of reserving it.
+        assert!(matches!(
+            decode_into(&[0b11000100], &tree, usize::MAX, &mut out),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_decode_with_tree_trailing_bytes() {
+        let text = b"this is an example of a huffman tree";
+        let (encoded, tree, _) = huffman_encode_with_tree(text);
+        let buffer = [&encoded[..], b"junk after the stream"].concat();
+        let (decoded, consumed) = decode_with_tree(&buffer, &tree, text.len()).unwrap();
+        assert_eq!(decoded, text);
+        assert_eq!(consumed, encoded.len());
+        assert!(consumed < buffer.len());
+        assert_eq!(&buffer[consumed..], b"junk after the stream");
+    }
+
+    #[test]
+    fn test_decode_iter() {
+        let mut r = StdRng::seed_from_u64(42);
+        for size in [1, 7, 100, 1024] {
+            let text = (&mut r)
+                .sample_iter(&Alphanumeric)
+                .take(size)
+                .collect::<Vec<u8>>();
+            let (encoded, tree, _) = huffman_encode_with_tree(&text);
+            let decoded = decode_iter(&encoded, &tree).take(size).collect::<Vec<u8>>();
+            assert_eq!(decoded, decode_with_tree(&encoded, &tree, size).unwrap().0);
+        }
+
+        let (encoded, tree, _) = huffman_encode_with_tree(b"AAA");
+        assert_eq!(decode_iter(&encoded, &tree).collect::<Vec<u8>>(), b"AAA");
+    }
+
+    #[test]
+    fn test_huffman_decode_random_strings() {
+        let mut r = StdRng::seed_from_u64(42);
+        for size in [2, 100, 1024, 1024 * 20] {
+            let text: String = (&mut r)
+                .sample_iter(&Alphanumeric)
+                .take(size)
+                .map(char::from)
+                .collect();
+            let (encoded, decode_codes, _) = huffman_encode(&text);
+            let result = huffman_decode(&encoded, &decode_codes, text.len()).unwrap();
+            assert_eq!(result, text);
+        }
+    }
+
+    #[test]
+    fn test_huffman_decode_string() {
+        let text = String::from("AABCBAD");
+        let (encoded, decode_codes) = huffman_encode_string(text.as_bytes());
+        let result = huffman_decode_string(&encoded, &decode_codes).unwrap();
+        assert_eq!(result, text);
+    }
+
+    #[test]
+    fn test_huffman_encode_bytes() {
+        let data = [0u8, 255, 128, 0];
+        let (encoded, decode_codes, bit_len) = huffman_encode_bytes(&data);
+        assert_eq!(bit_len, 6);
+        assert_eq!(encoded.len(), 1);
+        let result = huffman_decode_bytes(&encoded, &decode_codes, data.len()).unwrap();
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn test_huffman_decode_errors() {
+        let text = String::from("AABCBAD");
+        let (encoded, decode_codes, _) = huffman_encode(&text);
+        assert!(matches!(
+            huffman_decode(&encoded, &decode_codes, 20),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            huffman_decode_string(&[1, 0], &decode_codes),
+            Err(DecodeError::UnexpectedEof)
+        ));
+
+        let incomplete_codes = [(vec![b'1'], b'A'), (vec![b'0', b'0'], b'B')]
+            .into_iter()
+            .collect();
+        assert!(matches!(
+            huffman_decode_string(&[1, 0, 1], &incomplete_codes),
+            Err(DecodeError::InvalidCode)
+        ));
+
+        let (encoded, decode_codes, _) = huffman_encode_bytes(&[0xC3, 0x28]);
+        assert!(matches!(
+            huffman_decode(&encoded, &decode_codes, 2),
+            Err(DecodeError::InvalidUtf8)
+        ));
+    }
+
+    #[test]
+    fn test_huffman_encode_with_codes() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let codes = huffman_codes(&tree);
+        let (encoded, bit_len) = huffman_encode_with_codes(b"AAB", &codes).unwrap();
+        assert_eq!(bit_len, 4);
+        assert_eq!(encoded, vec![0b11000000]);
+
+        let (full, _, _) = huffman_encode_bytes(b"AABCBAD");
+        assert_eq!(encoded[0], full[0] & 0b11110000);
+    }
+
+    #[test]
+    fn test_huffman_encode_with_codes_missing_character() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let codes = huffman_codes(&tree);
+        for data in [&b"E"[..], b"ABE", b"ABCDABCDX"] {
+            let result = huffman_encode_with_codes(data, &codes);
+            assert!(matches!(
+                result,
+                Err(EncodeError::NoCodeForSymbol(c)) if c == *data.last().unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_huffman_encode_single_character() {
+        for (text, expected_encoded) in [("A", vec![0b10000000]), ("AAAA", vec![0b11110000])] {
+            let text = String::from(text);
+            let (encoded, decode_codes, bit_len) = huffman_encode(&text);
+            assert_eq!(encoded, expected_encoded);
+            assert_eq!(bit_len, text.len());
+            assert_eq!(decode_codes, [(vec![b'1'], b'A')].into_iter().collect());
+            assert_eq!(
+                huffman_decode(&encoded, &decode_codes, text.len()).unwrap(),
+                text
+            );
+
+            let (bits, decode_codes) = huffman_encode_string(text.as_bytes());
+            assert_eq!(huffman_decode_string(&bits, &decode_codes).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_huffman_encode_symbols() {
+        let data: Vec<u16> = vec![1000, 1000, 65535, 0, 1000, 65535, 42];
+        let (encoded, decode_codes, bit_len) = huffman_encode_symbols(&data);
+        assert_eq!(bit_len, 13);
+        assert_eq!(encoded.len(), 2);
+        let result = huffman_decode_symbols(&encoded, &decode_codes, data.len()).unwrap();
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn test_huffman_encode_chars() {
+        // One symbol, so one bit per character instead of a code per byte.
+        let (encoded, decode_codes, bit_len) = huffman_encode_chars("ééé");
+        assert_eq!(decode_codes.len(), 1);
+        assert_eq!(bit_len, 3);
+        assert_eq!(encoded, vec![0b1110_0000]);
+        let (_, byte_codes, _) = huffman_encode(&String::from("ééé"));
+        assert_eq!(byte_codes.len(), 2);
 
-    use super::*;
+        let text = "naïve café 🎉🎉";
+        let (encoded_text, decode_codes, _) = huffman_encode_chars(text);
+        assert!(decode_codes.values().any(|&c| c == '🎉'));
+        let decoded = huffman_decode_symbols(&encoded_text, &decode_codes, text.chars().count());
+        assert_eq!(decoded.unwrap().into_iter().collect::<String>(), text);
+    }
 
-    // #[test]
-    // fn really_big_string_encode() {
-    //     let r = StdRng::seed_from_u64(42);
-    //     let text: String = r
-    //         .sample_iter(&Alphanumeric)
-    //         .take(1024 * 20)
-    //         .map(char::from)
-    //         .collect();
-    //     let (encoded, _) = huffman_encode_string(&text);
-    // }
+    #[test]
+    fn test_huffman_encode_iter() {
+        for text in ["AABCBAD", "A", "this is an example of a huffman tree"] {
+            let (expected, decode_codes, expected_bit_len) = huffman_encode(&String::from(text));
+            let (encoded, codes, bit_len) = huffman_encode_iter(text.bytes());
+            assert_eq!((encoded, bit_len), (expected, expected_bit_len));
+            assert_eq!(decode_map(codes), decode_codes);
+        }
+
+        // Bytes that never exist as a slice.
+        let (encoded, codes, bit_len) = huffman_encode_iter((0..100u8).map(|i| i % 3));
+        assert_eq!(codes.len(), 3);
+        let data = (0..100u8).map(|i| i % 3).collect::<Vec<_>>();
+        assert_eq!(
+            decode_with_tree(&encoded, &build_decode_tree(&codes), data.len())
+                .unwrap()
+                .0,
+            data
+        );
+        assert_eq!(bit_len, huffman_encode_bytes(&data).2);
+    }
 
     #[test]
     fn test_huffman_encode() {
         let text = String::from("AABCBAD");
-        let (encoded, _) = huffman_encode(&text);
-        let expected_encoded = vec![0b11000100, 0b00001011];
+        let (encoded, _, bit_len) = huffman_encode(&text);
+        let expected_encoded = vec![0b11000100, 0b01011000];
         assert_eq!(encoded, expected_encoded);
+        assert_eq!(bit_len, 13);
+    }
+
+    #[test]
+    fn test_pack_bits() {
+        assert_eq!(pack_bits(&[1, 0, 1]), vec![0b10100000]);
+        assert_eq!(pack_bits(&[1, 1, 0, 0, 0, 1, 0, 1]), vec![0b11000101]);
+        assert_eq!(
+            pack_bits(&[1, 1, 0, 0, 0, 1, 0, 1, 1]),
+            vec![0b11000101, 0b10000000]
+        );
+        assert_eq!(pack_bits(&[]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_encode_packed() {
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..4096).map(|_| r.gen_range(0..40)).collect::<Vec<u8>>();
+        let codes = huffman_codes(&huffman_tree(&frequency_list(&frequency_counter(&data))));
+        let bits = encode_bits(&data, &codes);
+        assert_eq!(
+            encode_packed(&data, &codes),
+            Ok((pack_bits(&bits), bits.len()))
+        );
+
+        let long_codes = [(b'A', vec![b'1'; 40]), (b'B', vec![b'0'])]
+            .into_iter()
+            .collect();
+        let bits = encode_bits(b"ABA", &long_codes);
+        assert_eq!(
+            encode_packed(b"ABA", &long_codes),
+            Ok((pack_bits(&bits), bits.len()))
+        );
+        assert_eq!(encode_packed(b"ABCA", &long_codes), Err(b'C'));
+    }
+
+    #[test]
+    fn test_encode_packed_bytes() {
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let codes = huffman_codes(&huffman_tree(&byte_frequency_list(&data)));
+        assert_eq!(
+            encode_packed_bytes(&data, &codes),
+            encode_packed(&data, &codes)
+        );
+        assert_eq!(
+            byte_code_table(&codes).unwrap()[data[0] as usize],
+            Some(pack_code(&codes[&data[0]]))
+        );
+
+        let long_codes = [(b'A', vec![b'1'; 40]), (b'B', vec![b'0'])]
+            .into_iter()
+            .collect();
+        assert!(byte_code_table(&long_codes).is_none());
+        assert_eq!(
+            encode_packed_bytes(b"ABA", &long_codes),
+            encode_packed(b"ABA", &long_codes)
+        );
+        assert_eq!(encode_packed_bytes(b"ABCA", &long_codes), Err(b'C'));
+        let short_codes = huffman_codes(&huffman_tree(&[(b'A', 2), (b'B', 1)]));
+        assert_eq!(encode_packed_bytes(b"ABC", &short_codes), Err(b'C'));
+    }
+
+    #[test]
+    fn test_huffman_encode_pads_last_byte() {
+        let text = String::from("ABB");
+        let (encoded, _, bit_len) = huffman_encode(&text);
+        assert_eq!(bit_len, 3);
+        assert_eq!(encoded, vec![0b10000000]);
+    }
+
+    #[test]
+    fn test_huffman_encode_bit_len() {
+        let text = String::from("the quick brown fox jumps over the lazy dog");
+        let (_, decode_codes, bit_len) = huffman_encode(&text);
+        let code_lengths = decode_codes
+            .iter()
+            .map(|(code, c)| (*c, code.len()))
+            .collect::<Map<_, _>>();
+        let expected = text.bytes().map(|c| code_lengths[&c]).sum::<usize>();
+        assert_eq!(bit_len, expected);
     }
 
     #[test]
     fn test_huffman_encode_string() {
         let text = String::from("AABCBAD");
-        let (encoded, decode_codes) = huffman_encode_string(&text);
+        let (encoded, decode_codes) = huffman_encode_string(text.as_bytes());
         let expected_encoded = vec![1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 1, 1];
         let expected_decode_codes = [
             (vec![b'1'], b'A'),
@@ -206,6 +2030,65 @@ mod tests {
         assert_eq!(decode_codes, expected_decode_codes);
     }
 
+    #[test]
+    fn test_huffman_encode_full() {
+        let text = String::from("AABCBAD");
+        let result = huffman_encode_full(text.as_bytes());
+        let (packed, decode_codes, bit_len) = huffman_encode(&text);
+        assert_eq!(result.packed, packed);
+        assert_eq!(result.bit_len, bit_len);
+        assert_eq!(decode_map(result.codes.clone()), decode_codes);
+        let (bits, _) = huffman_encode_string(text.as_bytes());
+        assert_eq!(result.bits, bits);
+        assert_eq!(result.bits.len(), result.bit_len);
+        assert_eq!(pack_bits(&result.bits), result.packed);
+    }
+
+    // The previous recursive implementation of `huffman_codes`.
+    fn recursive_huffman_codes(tree: &HuffmanTreeNode) -> HuffmanCode {
+        fn rec_huffman_codes(
+            branch: &Option<Box<HuffmanTreeNode>>,
+            code: &[u8],
+            codes: &mut HuffmanCode,
+        ) {
+            if let Some(node_box) = branch {
+                match node_box.as_ref() {
+                    HuffmanTreeNode::Character(node) => {
+                        codes.insert(node.character, code.to_vec());
+                    }
+                    HuffmanTreeNode::Value(node) => {
+                        rec_huffman_codes(&node.left, &[code, b"0"].concat(), codes);
+                        rec_huffman_codes(&node.right, &[code, b"1"].concat(), codes);
+                    }
+                }
+            }
+        }
+        let mut codes = HuffmanCode::new();
+        match tree {
+            HuffmanTreeNode::Character(node) => {
+                codes.insert(node.character, vec![b'1']);
+            }
+            HuffmanTreeNode::Value(node) => {
+                rec_huffman_codes(&node.left, b"0", &mut codes);
+                rec_huffman_codes(&node.right, b"1", &mut codes);
+            }
+        }
+        codes
+    }
+
+    #[test]
+    fn test_huffman_codes_matches_recursive() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096)
+            .map(|_| r.gen_range(0..=255u8) & r.gen_range(0..=255u8))
+            .collect::<Vec<u8>>();
+        let texts: [&[u8]; 4] = [b"A", b"AABCBAD", b"the quick brown fox", &random];
+        for text in texts {
+            let tree = huffman_tree(&frequency_list(&frequency_counter(text)));
+            assert_eq!(huffman_codes(&tree), recursive_huffman_codes(&tree));
+        }
+    }
+
     #[test]
     fn test_huffman_codes() {
         let frequency_list = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
@@ -223,6 +2106,453 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    // The previous builder, which chained one leaf at a time down the sorted list.
+    fn sequential_huffman_tree(frequency_list: &[(u8, usize)]) -> HuffmanTreeNode {
+        let character_node = HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+            character: frequency_list[0].0,
+            frequency: frequency_list[0].1,
+        });
+        if frequency_list.len() == 1 {
+            return character_node;
+        }
+        let value_node = sequential_huffman_tree(&frequency_list[1..]);
+        let (left, right) = if character_node.value() >= value_node.value() {
+            (character_node, value_node)
+        } else {
+            (value_node, character_node)
+        };
+        HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+            value: left.value() + right.value(),
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+        })
+    }
+
+    fn total_bits(frequency_list: &[(u8, usize)], tree: &HuffmanTreeNode) -> usize {
+        let codes = huffman_codes(tree);
+        frequency_list.iter().map(|(c, f)| f * codes[c].len()).sum()
+    }
+
+    #[test]
+    fn test_pack_code() {
+        assert_eq!(pack_code(b"1"), (0b1, 1));
+        assert_eq!(pack_code(b"010"), (0b010, 3));
+        assert_eq!(pack_code(b""), (0, 0));
+        assert_eq!(pack_code(&[b'1'; 32]), (u32::MAX, 32));
+    }
+
+    #[test]
+    fn test_packed_huffman_codes() {
+        let frequency_list = frequency_list(&frequency_counter(b"AABCBAD"));
+        let tree = huffman_tree(&frequency_list);
+        let result = packed_huffman_codes(&huffman_codes(&tree));
+        let expected = [
+            (b'A', (0b1, 1)),
+            (b'B', (0b00, 2)),
+            (b'C', (0b010, 3)),
+            (b'D', (0b011, 3)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_code_lengths() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let expected = [(b'A', 1), (b'B', 2), (b'C', 3), (b'D', 3)]
+            .into_iter()
+            .collect();
+        assert_eq!(code_lengths(&tree), expected);
+
+        let tree = huffman_tree(&[(b'A', 4)]);
+        assert_eq!(code_lengths(&tree), [(b'A', 1)].into_iter().collect());
+    }
+
+    #[test]
+    fn test_code_length_table() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let table = code_length_table(&tree);
+        assert_eq!(table[b'A' as usize..=b'D' as usize], [1, 2, 3, 3]);
+        assert_eq!(table.iter().filter(|&&length| length > 0).count(), 4);
+        assert_eq!(
+            table.iter().map(|&length| length as usize).sum::<usize>(),
+            9
+        );
+    }
+
+    #[test]
+    fn test_max_code_length() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        assert_eq!(max_code_length(&tree), 3);
+        let single = huffman_tree(&frequency_list(&frequency_counter(b"AAA")));
+        assert_eq!(max_code_length(&single), 1);
+
+        let all = (0..=255).collect::<Vec<u8>>();
+        let tree = huffman_tree(&frequency_list(&frequency_counter(&all)));
+        assert_eq!(max_code_length(&tree), 8);
+    }
+
+    #[test]
+    fn test_length_limited_codes() {
+        let mut fibonacci = vec![1usize, 1];
+        while fibonacci.len() < 20 {
+            fibonacci.push(fibonacci[fibonacci.len() - 1] + fibonacci[fibonacci.len() - 2]);
+        }
+        let counter = fibonacci
+            .iter()
+            .enumerate()
+            .map(|(i, &f)| (b'a' + i as u8, f))
+            .collect::<Map<_, _>>();
+        let unlimited = code_lengths(&huffman_tree(&frequency_list(&counter)));
+        assert_eq!(unlimited.values().max(), Some(&19));
+
+        let codes = length_limited_codes(&counter, 15).unwrap();
+        assert_eq!(codes.len(), 20);
+        assert!(codes.values().all(|code| code.len() <= 15));
+
+        let data = (b'a'..b'a' + 20).collect::<Vec<u8>>();
+        let (encoded, bit_len) = huffman_encode_with_codes(&data, &codes).unwrap();
+        let decoded = decode_characters(
+            unpack_bits(&encoded).take(bit_len),
+            &build_decode_tree(&codes),
+            None,
+        );
+        assert_eq!(decoded.unwrap(), data);
+    }
+
+    #[test]
+    fn test_length_limited_codes_fit() {
+        let counter = frequency_counter(b"AABCBAD");
+        let codes = length_limited_codes(&counter, 15).unwrap();
+        let expected = canonical_codes(&huffman_tree(&frequency_list(&counter)));
+        assert_eq!(codes, expected);
+
+        let codes = length_limited_codes(&counter, 2).unwrap();
+        assert!(codes.values().all(|code| code.len() == 2));
+
+        assert!(matches!(
+            length_limited_codes(&counter, 1),
+            Err(EncodeError::CodeLengthLimitTooSmall(1))
+        ));
+        let single = length_limited_codes(&frequency_counter(b"AAA"), 1).unwrap();
+        assert_eq!(single, [(b'A', vec![b'0'])].into_iter().collect());
+        assert!(matches!(
+            length_limited_codes(&frequency_counter(b"AAA"), 0),
+            Err(EncodeError::CodeLengthLimitTooSmall(0))
+        ));
+    }
+
+    #[test]
+    fn test_canonical_codes() {
+        let text = b"AABCBAD";
+        let frequency_list = frequency_list(&frequency_counter(text));
+        let tree = huffman_tree(&frequency_list);
+        let result = canonical_codes(&tree);
+        let expected = [
+            (b'A', vec![b'0']),
+            (b'B', vec![b'1', b'0']),
+            (b'C', vec![b'1', b'1', b'0']),
+            (b'D', vec![b'1', b'1', b'1']),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(result, expected);
+
+        let encoded = text
+            .iter()
+            .flat_map(|c| result[c].iter().map(|&b| if b == b'1' { 1 } else { 0 }))
+            .collect::<Vec<u8>>();
+        let decode_codes = result
+            .into_iter()
+            .map(|(c, code)| (code, c))
+            .collect::<Map<_, _>>();
+        assert_eq!(
+            huffman_decode_string(&encoded, &decode_codes).unwrap(),
+            "AABCBAD"
+        );
+    }
+
+    #[test]
+    fn test_huffman_tree_is_no_larger_than_sequential() {
+        let skewed = [
+            (b'A', 40),
+            (b'B', 20),
+            (b'C', 12),
+            (b'D', 10),
+            (b'E', 8),
+            (b'F', 5),
+            (b'G', 3),
+            (b'H', 2),
+        ];
+        let uniform = [(b'A', 5), (b'B', 5), (b'C', 5), (b'D', 5)];
+        for frequency_list in [&skewed[..], &uniform[..]] {
+            let heap_bits = total_bits(frequency_list, &huffman_tree(frequency_list));
+            let sequential_bits =
+                total_bits(frequency_list, &sequential_huffman_tree(frequency_list));
+            assert!(heap_bits <= sequential_bits);
+        }
+        assert_eq!(total_bits(&skewed, &huffman_tree(&skewed)), 253);
+        assert_eq!(total_bits(&skewed, &sequential_huffman_tree(&skewed)), 261);
+        assert_eq!(total_bits(&uniform, &huffman_tree(&uniform)), 40);
+        assert_eq!(total_bits(&uniform, &sequential_huffman_tree(&uniform)), 45);
+    }
+
+    // Smallest `Σ frequency · depth` over every full binary tree with these
+    // leaves. Each such tree is built by merging two subtrees at a time, and
+    // every merge adds their combined weight to the total, so trying every
+    // pair at every step covers them all.
+    fn min_total_bits(weights: &[usize]) -> usize {
+        if weights.len() < 2 {
+            return 0;
+        }
+        let mut best = usize::MAX;
+        for i in 0..weights.len() {
+            for j in i + 1..weights.len() {
+                let mut merged = weights.to_vec();
+                let second = merged.swap_remove(j);
+                merged[i] += second;
+                best = best.min(weights[i] + weights[j] + min_total_bits(&merged));
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn test_huffman_tree_is_optimal() {
+        let mut r = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let len = r.gen_range(2..=6);
+            let frequency_list = (b'A'..)
+                .take(len)
+                .map(|c| (c, r.gen_range(0..50)))
+                .collect::<Vec<_>>();
+            let tree = huffman_tree(&frequency_list);
+
+            // Kraft's equality: every node is full, no code is wasted.
+            let lengths = code_lengths(&tree);
+            assert_eq!(
+                lengths.values().map(|&l| 1 << (16 - l)).sum::<usize>(),
+                1 << 16
+            );
+
+            let weights = frequency_list.iter().map(|&(_, f)| f).collect::<Vec<_>>();
+            assert_eq!(
+                total_bits(&frequency_list, &tree),
+                min_total_bits(&weights),
+                "{frequency_list:?}"
+            );
+        }
+    }
+
+    fn without_weights(tree: &HuffmanTreeNode) -> HuffmanTreeNode {
+        match tree {
+            HuffmanTreeNode::Character(node) => {
+                HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+                    character: node.character,
+                    frequency: 0,
+                })
+            }
+            HuffmanTreeNode::Value(node) => HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+                value: 0,
+                left: node.left.as_deref().map(|n| Box::new(without_weights(n))),
+                right: node.right.as_deref().map(|n| Box::new(without_weights(n))),
+            }),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_frequency_counter() {
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..300_000)
+            .map(|_| r.gen_range(0..200))
+            .collect::<Vec<u8>>();
+        assert_eq!(par_frequency_counter(&data), frequency_counter(&data));
+        assert!(par_frequency_counter(b"").is_empty());
+    }
+
+    #[test]
+    fn test_byte_frequency_list() {
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..4096).map(|_| r.gen_range(0..64)).collect::<Vec<u8>>();
+        for data in [&b""[..], b"AABCBAD", &data] {
+            assert_eq!(
+                byte_frequency_list(data),
+                frequency_list(&frequency_counter(data))
+            );
+        }
+    }
+
+    #[test]
+    fn test_huffman_encode_with_tree() {
+        for text in [
+            &b"A"[..],
+            b"AABCBAD",
+            b"the quick brown fox jumps over the lazy dog",
+        ] {
+            let (encoded, tree, bit_len) = huffman_encode_with_tree(text);
+            assert_eq!(
+                tree,
+                huffman_tree(&frequency_list(&frequency_counter(text)))
+            );
+            let (expected, _, expected_bit_len) = huffman_encode_bytes(text);
+            assert_eq!((encoded, bit_len), (expected, expected_bit_len));
+        }
+    }
+
+    #[test]
+    fn test_kraft_sum() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        assert_eq!(kraft_sum(&huffman_codes(&tree)), 1.0);
+        assert_eq!(kraft_sum(&english_codes()), 1.0);
+        assert_eq!(kraft_sum(&HuffmanCode::new()), 0.0);
+
+        // Nothing starts with 11.
+        let incomplete = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', b"10".to_vec())]);
+        assert_eq!(kraft_sum(&incomplete), 0.75);
+        let too_short = HuffmanCode::from([
+            (b'A', b"0".to_vec()),
+            (b'B', b"1".to_vec()),
+            (b'C', b"10".to_vec()),
+        ]);
+        assert_eq!(kraft_sum(&too_short), 1.25);
+    }
+
+    #[test]
+    fn test_codes_as_strings() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let strings = codes_as_strings(&huffman_codes(&tree));
+        assert_eq!(strings.len(), 4);
+        assert_eq!(strings[&b'A'], "1");
+        assert_eq!(strings[&b'B'], "00");
+        assert_eq!(strings[&b'C'], "010");
+        assert_eq!(strings[&b'D'], "011");
+        assert!(codes_as_strings(&HuffmanCode::new()).is_empty());
+    }
+
+    #[test]
+    fn test_validate_prefix_free() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        assert_eq!(validate_prefix_free(&huffman_codes(&tree)), Ok(()));
+        assert_eq!(validate_prefix_free(&HuffmanCode::new()), Ok(()));
+
+        let codes = HuffmanCode::from([(b'A', b"1".to_vec()), (b'B', b"10".to_vec())]);
+        assert_eq!(
+            validate_prefix_free(&codes),
+            Err(ValidationError::NotPrefixFree(b'A', b'B'))
+        );
+        let codes = HuffmanCode::from([
+            (b'A', b"0".to_vec()),
+            (b'B', b"11".to_vec()),
+            (b'C', b"10".to_vec()),
+            (b'D', b"110".to_vec()),
+        ]);
+        assert_eq!(
+            validate_prefix_free(&codes),
+            Err(ValidationError::NotPrefixFree(b'B', b'D'))
+        );
+        let codes = HuffmanCode::from([(b'A', b"1".to_vec()), (b'B', b"1".to_vec())]);
+        assert_eq!(
+            validate_prefix_free(&codes),
+            Err(ValidationError::NotPrefixFree(b'A', b'B'))
+        );
+        let codes = HuffmanCode::from([(b'A', Vec::new())]);
+        assert_eq!(
+            validate_prefix_free(&codes),
+            Err(ValidationError::EmptyCode(b'A'))
+        );
+    }
+
+    #[test]
+    fn test_serialize_code_lengths() {
+        let lengths = Map::from([(b'A', 1), (b'B', 2), (b'C', 3), (b'D', 3)]);
+        let serialized = serialize_code_lengths(&lengths);
+        // A run of 65 missing bytes, then four lengths.
+        assert_eq!(serialized, [0x04, 0x01, 0x23, 0x30]);
+        assert_eq!(deserialize_code_lengths(&serialized).unwrap(), lengths);
+
+        // Three codes of one bit do not fit in a tree.
+        assert!(matches!(
+            deserialize_code_lengths(&[0x11, 0x10]),
+            Err(DecodeError::BadHeader)
+        ));
+        assert!(matches!(
+            deserialize_code_lengths(&[0x0f, 0xf1]),
+            Err(DecodeError::BadHeader)
+        ));
+        assert!(matches!(
+            deserialize_code_lengths(&[]),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_build_decode_tree() {
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        assert_eq!(
+            build_decode_tree(&huffman_codes(&tree)),
+            without_weights(&tree)
+        );
+
+        // A code behind another one's leaf can never be decoded and is dropped.
+        let codes = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', b"01".to_vec())]);
+        let expected = HuffmanTreeNode::Value(HuffmanTreeNodeValue {
+            value: 0,
+            left: Some(Box::new(HuffmanTreeNode::Character(
+                HuffmanTreeNodeCharacter {
+                    character: b'A',
+                    frequency: 0,
+                },
+            ))),
+            right: None,
+        });
+        assert_eq!(build_decode_tree(&codes), expected);
+        assert!(matches!(
+            decode_characters([0, 1].into_iter(), &expected, None),
+            Err(DecodeError::InvalidCode)
+        ));
+    }
+
+    #[test]
+    fn test_serialize_tree() {
+        let frequency_list = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
+        let tree = huffman_tree(&frequency_list);
+        let serialized = serialize_tree(&tree, 8);
+        // 3 internal nodes + 4 leaves of 9 bits each = 39 bits.
+        assert_eq!(serialized.len(), 5);
+        assert_eq!(
+            deserialize_tree(&serialized, 8).unwrap(),
+            without_weights(&tree)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_tree_truncated() {
+        let frequency_list = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
+        let serialized = serialize_tree(&huffman_tree(&frequency_list), 8);
+        assert!(matches!(
+            deserialize_tree::<u8>(&serialized[..3], 8),
+            Err(DecodeError::TruncatedTree)
+        ));
+        assert!(matches!(
+            deserialize_tree::<u8>(&[0; 64], 8),
+            Err(DecodeError::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_serialize_tree_single_character() {
+        let tree = huffman_tree(&[(0xFF, 10)]);
+        let serialized = serialize_tree(&tree, 8);
+        assert_eq!(serialized, vec![0b11111111, 0b10000000]);
+        assert_eq!(
+            deserialize_tree(&serialized, 8).unwrap(),
+            without_weights(&tree)
+        );
+    }
+
     #[test]
     fn test_huffman_tree() {
         let frequency_list = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)];
@@ -263,9 +2593,264 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_code_for() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        assert_eq!(tree.code_for(b'A'), Some(vec![b'1']));
+        assert_eq!(tree.code_for(b'D'), Some(b"011".to_vec()));
+        assert_eq!(tree.code_for(b'Z'), None);
+        for (c, code) in huffman_codes(&tree) {
+            assert_eq!(tree.code_for(c), Some(code));
+        }
+
+        let leaf = huffman_tree(&[(b'A', 4)]);
+        assert_eq!(leaf.code_for(b'A'), Some(vec![b'1']));
+        assert_eq!(leaf.code_for(b'B'), None);
+    }
+
+    #[test]
+    fn test_symbols_in_code_order() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        assert_eq!(tree.symbols_in_code_order(), b"ABCD");
+
+        // Shorter codes come first whatever the symbol, and canonical codes
+        // count up in this order.
+        let tree = huffman_tree(&[(b'Z', 5), (b'Y', 2), (b'A', 1), (b'B', 1)]);
+        let symbols = tree.symbols_in_code_order();
+        assert_eq!(symbols, b"ZYAB");
+        let codes = canonical_codes(&tree);
+        assert!(symbols.windows(2).all(|pair| {
+            let (a, b) = (&codes[&pair[0]], &codes[&pair[1]]);
+            (a.len(), a) < (b.len(), b)
+        }));
+
+        assert_eq!(huffman_tree(&[(b'A', 4)]).symbols_in_code_order(), b"A");
+    }
+
+    #[test]
+    fn test_huffman_tree_clone() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let copy = tree.clone();
+        assert_eq!(copy, tree);
+        assert_eq!(huffman_codes(&copy), huffman_codes(&tree));
+    }
+
+    #[test]
+    fn test_huffman_tree_hash() {
+        use std::{collections::HashMap, hash::BuildHasher};
+
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let same = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        let hasher = std::hash::RandomState::new();
+        assert_eq!(hasher.hash_one(&tree), hasher.hash_one(&same));
+
+        let mut cache = HashMap::new();
+        cache.insert(tree, 1);
+        assert_eq!(cache.insert(same, 2), Some(1));
+        cache.insert(huffman_tree(&[(b'A', 3), (b'B', 3)]), 3);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_huffman_tree_serde() {
+        let tree = huffman_tree(&frequency_list(&frequency_counter(b"AABCBAD")));
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(
+            serde_json::from_str::<HuffmanTreeNode>(&json).unwrap(),
+            tree
+        );
+    }
+
+    #[test]
+    fn test_huffman_tree_display() {
+        let tree = huffman_tree(&[(b'A', 2), (b'B', 1)]);
+        assert_eq!(tree.to_string(), "(3)\n|-0- 'A': 2\n`-1- 'B': 1");
+
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'\n', 1)]);
+        let expected = "\
+(7)
+|-0- (4)
+|    |-0- (2)
+|    |    |-0- '\\n': 1
+|    |    `-1- 'C': 1
+|    `-1- 'B': 2
+`-1- 'A': 3";
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn test_huffman_tree_shape() {
+        let tree = huffman_tree(&[(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]);
+        assert_eq!(tree.leaf_count(), 4);
+        assert_eq!(tree.internal_count(), 3);
+        assert_eq!(tree.depth(), 3);
+
+        let leaf = huffman_tree(&[(b'A', 3)]);
+        assert_eq!(leaf.leaf_count(), 1);
+        assert_eq!(leaf.internal_count(), 0);
+        assert_eq!(leaf.depth(), 0);
+
+        let frequency_list = (0..=255).map(|c| (c, 1 << (c / 8))).collect::<Vec<_>>();
+        let sequential = sequential_huffman_tree(&frequency_list);
+        assert_eq!(sequential.leaf_count(), 256);
+        assert_eq!(sequential.depth(), 255);
+    }
+
+    #[test]
+    fn test_huffman_tree_all_bytes() {
+        let uniform = (0..=255).map(|c| (c, 1)).collect::<Vec<_>>();
+        let tree = huffman_tree(&uniform);
+        assert_eq!(tree.leaf_count(), 256);
+        assert_eq!(tree.depth(), 8);
+        assert!(huffman_codes(&tree).values().all(|code| code.len() == 8));
+
+        // The deepest tree over bytes, one leaf per level.
+        let frequency_list = (0..=255).map(|c| (c, 1 << (c / 8))).collect::<Vec<_>>();
+        let deep = sequential_huffman_tree(&frequency_list);
+        let codes = huffman_codes(&deep);
+        assert_eq!(codes.values().map(Vec::len).max(), Some(255));
+        let restored = deserialize_tree::<u8>(&serialize_tree(&deep, 8), 8).unwrap();
+        assert_eq!(huffman_codes(&restored), codes);
+        let data = (0..=255).collect::<Vec<u8>>();
+        let (encoded, _) = huffman_encode_with_codes(&data, &codes).unwrap();
+        assert_eq!(decode_with_tree(&encoded, &restored, 256).unwrap().0, data);
+    }
+
+    #[test]
+    fn test_huffman_tree_equal_frequencies() {
+        let frequency_list = (b'A'..=b'H').map(|c| (c, 5)).collect::<Vec<_>>();
+        let expected = (b'A'..=b'H')
+            .map(|c| {
+                let i = c - b'A';
+                let code = (0..3)
+                    .rev()
+                    .map(|bit| if i >> bit & 1 == 1 { b'1' } else { b'0' })
+                    .collect();
+                (c, code)
+            })
+            .collect::<HuffmanCode>();
+        assert_eq!(huffman_codes(&huffman_tree(&frequency_list)), expected);
+
+        let reversed = frequency_list.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(huffman_codes(&huffman_tree(&reversed)), expected);
+        for _ in 0..10 {
+            let counter = frequency_list.iter().cloned().collect::<Map<_, _>>();
+            let shuffled = counter.into_iter().collect::<Vec<_>>();
+            assert_eq!(huffman_codes(&huffman_tree(&shuffled)), expected);
+        }
+    }
+
+    #[test]
+    fn test_huffman_tree_node_ord() {
+        let leaf = |character, frequency| {
+            HuffmanTreeNode::Character(HuffmanTreeNodeCharacter {
+                character,
+                frequency,
+            })
+        };
+        let mut heap = [
+            leaf(b'C', 1),
+            leaf(b'A', 3),
+            huffman_tree(&[(b'D', 1), (b'E', 1)]),
+            leaf(b'B', 2),
+        ]
+        .into_iter()
+        .map(core::cmp::Reverse)
+        .collect::<BinaryHeap<_>>();
+        let popped = core::iter::from_fn(|| heap.pop())
+            .map(|node| (node.0.value(), node.0.min_character()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            popped,
+            [
+                (1, Some(b'C')),
+                (2, Some(b'B')),
+                (2, Some(b'D')),
+                (3, Some(b'A'))
+            ]
+        );
+
+        // Same value and smallest character, told apart by shape.
+        let tree = huffman_tree(&[(b'A', 1), (b'B', 1)]);
+        assert!(leaf(b'A', 2) < tree);
+        assert_eq!(
+            tree.cmp(&huffman_tree(&[(b'A', 1), (b'B', 1)])),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_huffman_tree_complete() {
+        let sample = b"this is an example of a huffman tree";
+        let tree = huffman_tree_complete(&frequency_counter(sample));
+        let codes = huffman_codes(&tree);
+        assert_eq!(codes.len(), 256);
+        assert_eq!(tree.leaf_count(), 256);
+        // Seen bytes keep shorter codes than the ones filled in.
+        assert!(codes[&b' '].len() < codes[&b'z'].len());
+
+        for data in [&sample[..], &[0x00, 0xff, b'z']] {
+            let (encoded, _) = huffman_encode_with_codes(data, &codes).unwrap();
+            let decoded = decode_with_tree(&encoded, &tree, data.len()).unwrap().0;
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_missing_symbols() {
+        let missing = missing_symbols(&frequency_counter(b"AABCBAD"));
+        assert_eq!(missing.len(), 252);
+        assert!(missing.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(!missing.iter().any(|c| b"ABCD".contains(c)));
+
+        let counter = [(0x00, 0), (0x01, 1)].into_iter().collect();
+        // A count of 0 is as good as no entry.
+        let expected = [0].into_iter().chain(2..=255).collect::<Vec<u8>>();
+        assert_eq!(missing_symbols(&counter), expected);
+        assert_eq!(missing_symbols(&Map::new()), (0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_huffman_tree_from_weights() {
+        let weights = [(b'A', 50), (b'B', 30), (b'C', 20), (b'Z', 0)]
+            .into_iter()
+            .collect();
+        let tree = huffman_tree_from_weights(&weights).unwrap();
+        let codes = huffman_codes(&tree);
+        assert_eq!(codes.len(), 4);
+        assert_eq!(codes[&b'Z'].len(), 3);
+
+        let (encoded, bit_len) = huffman_encode_with_codes(b"ZAZ", &codes).unwrap();
+        let decoded = decode_characters(
+            unpack_bits(&encoded).take(bit_len),
+            &build_decode_tree(&codes),
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded, b"ZAZ");
+    }
+
+    #[test]
+    fn test_huffman_tree_from_weights_overflow() {
+        let weights = [(b'A', usize::MAX - 1), (b'B', 1), (b'C', 1)]
+            .into_iter()
+            .collect();
+        assert!(matches!(
+            huffman_tree_from_weights(&weights),
+            Err(EncodeError::FrequencyOverflow)
+        ));
+
+        let weights = [(b'A', usize::MAX - 1), (b'B', 1)].into_iter().collect();
+        assert_eq!(
+            huffman_tree_from_weights(&weights).unwrap().value(),
+            usize::MAX
+        );
+    }
+
     #[test]
     fn test_frequency_list() {
-        let frequency_counter: HashMap<u8, usize> = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]
+        let frequency_counter: Map<u8, usize> = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]
             .into_iter()
             .collect();
         let result = frequency_list(&frequency_counter);
@@ -273,13 +2858,80 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_frequency_list_ties() {
+        let frequency_counter: Map<u8, usize> = [(b'C', 2), (b'A', 1), (b'B', 2), (b'D', 1)]
+            .into_iter()
+            .collect();
+        let result = frequency_list(&frequency_counter);
+        let expected = vec![(b'B', 2), (b'C', 2), (b'A', 1), (b'D', 1)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_same_histogram_same_codes() {
+        let texts: [&[u8]; 3] = [b"AABCBAD", b"DABACBA", b"BCDAAAB"];
+        let codes = texts
+            .iter()
+            .map(|text| huffman_codes(&huffman_tree(&frequency_list(&frequency_counter(text)))))
+            .collect::<Vec<_>>();
+        assert!(codes.iter().all(|c| *c == codes[0]));
+
+        let reversed: Map<u8, usize> = frequency_counter(texts[0])
+            .into_iter()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let result = huffman_codes(&huffman_tree(&frequency_list(&reversed)));
+        assert_eq!(result, codes[0]);
+    }
+
+    #[test]
+    fn test_huffman_tree_deterministic() {
+        // Each map gets its own hasher keys, so iteration order changes from
+        // one build to the next; many bytes tie on their counts.
+        let mut r = StdRng::seed_from_u64(42);
+        let skewed = (0..4096)
+            .map(|_| (r.gen::<f64>().powi(2) * 64.0) as u8)
+            .collect::<Vec<u8>>();
+        let all = (0..=255).cycle().take(1024).collect::<Vec<u8>>();
+        for data in [&b"AABCBAD"[..], &skewed, &all] {
+            let build = || {
+                let counter = frequency_counter(data);
+                let tree = huffman_tree(&frequency_list(&counter));
+                let mut codes = huffman_codes(&tree).into_iter().collect::<Vec<_>>();
+                codes.sort();
+                let mut limited = length_limited_codes(&counter, 10)
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                limited.sort();
+                (serialize_tree(&tree, 8), codes, limited)
+            };
+            let first = build();
+            for _ in 0..100 {
+                assert_eq!(build(), first);
+            }
+        }
+    }
+
     #[test]
     fn test_frequency_counter() {
         let text = "AABCBAD".to_string();
-        let result = frequency_counter(&text);
-        let expected: HashMap<u8, usize> = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]
+        let result = frequency_counter(text.as_bytes());
+        let expected: Map<u8, usize> = [(b'A', 3), (b'B', 2), (b'C', 1), (b'D', 1)]
             .into_iter()
             .collect();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_combine_histograms() {
+        let combined = combine_histograms(&frequency_counter(b"AAB"), &frequency_counter(b"BCC"));
+        let expected: Map<u8, usize> = [(b'A', 2), (b'B', 2), (b'C', 2)].into_iter().collect();
+        assert_eq!(combined, expected);
+        assert_eq!(combined, frequency_counter(b"AABBCC"));
+        assert_eq!(combine_histograms(&combined, &Map::new()), combined);
+    }
 }
@@ -0,0 +1,88 @@
+// Round-trip checks of every encoder against its decoder on inputs around
+// the byte boundaries of the packed bitstream, where padding bugs show up.
+
+use alloc::vec::Vec;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    adaptive_decode, adaptive_encode, compress, compress_blocks, compress_canonical, compress_mtf,
+    compress_rle, compress_with_eof, compress_with_escape, decode_iter, decode_with_tree,
+    decompress, decompress_blocks, decompress_canonical, decompress_with_eof,
+    decompress_with_escape, huffman_build, huffman_decode_bytes, huffman_encode_bytes,
+    huffman_encode_with_tree, HuffmanEncoder,
+};
+
+const LENGTHS: [usize; 9] = [0, 1, 7, 8, 9, 255, 256, 257, 4096];
+
+// For each length, a buffer over a few bytes, where codes are short and end
+// anywhere in a byte, and one over every byte value.
+fn inputs() -> Vec<Vec<u8>> {
+    let mut r = StdRng::seed_from_u64(42);
+    let random_lengths = (0..8).map(|_| r.gen_range(0..2048)).collect::<Vec<_>>();
+    let mut inputs = Vec::new();
+    for len in LENGTHS.into_iter().chain(random_lengths) {
+        inputs.push((0..len).map(|_| r.gen_range(b'A'..=b'E')).collect());
+        inputs.push((0..len).map(|_| r.gen()).collect());
+    }
+    inputs.push(b"A".repeat(9));
+    inputs
+}
+
+#[test]
+fn test_round_trip_bitstream() {
+    for data in inputs().into_iter().filter(|data| !data.is_empty()) {
+        let (encoded, codes, bit_len) = huffman_encode_bytes(&data);
+        assert_eq!(encoded.len(), bit_len.div_ceil(8));
+        assert_eq!(
+            huffman_decode_bytes(&encoded, &codes, data.len()).unwrap(),
+            data
+        );
+
+        let (encoded, tree, _) = huffman_encode_with_tree(&data);
+        assert_eq!(
+            decode_with_tree(&encoded, &tree, data.len()).unwrap(),
+            (data.clone(), encoded.len())
+        );
+        let decoded = decode_iter(&encoded, &tree)
+            .take(data.len())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, data);
+
+        let model = huffman_build(&data);
+        let (encoded, _) = model.encode(&data).unwrap();
+        assert_eq!(model.decode(&encoded, data.len()).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_round_trip_formats() {
+    for data in inputs() {
+        assert_eq!(decompress(&compress(&data)).unwrap(), data);
+        assert_eq!(decompress(&compress_rle(&data)).unwrap(), data);
+        assert_eq!(decompress(&compress_mtf(&data)).unwrap(), data);
+        assert_eq!(
+            decompress_canonical(&compress_canonical(&data)).unwrap(),
+            data
+        );
+        assert_eq!(
+            decompress_with_eof(&compress_with_eof(&data)).unwrap(),
+            data
+        );
+        assert_eq!(
+            decompress_with_escape(&compress_with_escape(&data, 4)).unwrap(),
+            data
+        );
+        assert_eq!(
+            decompress_blocks(&compress_blocks(&data, 100)).unwrap(),
+            data
+        );
+        assert_eq!(adaptive_decode(&adaptive_encode(&data)).unwrap(), data);
+
+        let encoder = HuffmanEncoder::builder()
+            .max_code_length(8)
+            .with_checksum(false)
+            .build();
+        assert_eq!(encoder.decode(&encoder.encode(&data)).unwrap(), data);
+    }
+}
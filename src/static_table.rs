@@ -0,0 +1,148 @@
+// Encoding with a code table both sides already have, as HPACK does for HTTP
+// headers, so nothing but the bitstream is sent.
+
+use alloc::vec::Vec;
+
+use crate::{
+    bitio::BitReader, build_decode_tree, decode_bits, huffman_encode_with_codes,
+    length_limited_codes, DecodeError, EncodeError, HuffmanCode, HuffmanModel, Map,
+};
+
+/// Relative frequencies of lowercase letters and common punctuation in
+/// English prose, the model behind [`english_codes`].
+pub const ENGLISH_FREQUENCIES: [(u8, usize); 38] = [
+    (b' ', 1800),
+    (b'e', 1000),
+    (b't', 740),
+    (b'a', 650),
+    (b'o', 610),
+    (b'i', 570),
+    (b'n', 560),
+    (b's', 520),
+    (b'h', 500),
+    (b'r', 490),
+    (b'd', 340),
+    (b'l', 330),
+    (b'c', 220),
+    (b'u', 220),
+    (b'm', 200),
+    (b'w', 190),
+    (b'f', 180),
+    (b'g', 160),
+    (b'y', 160),
+    (b'p', 150),
+    (b'b', 120),
+    (b',', 100),
+    (b'.', 90),
+    (b'v', 80),
+    (b'k', 60),
+    (b'\n', 40),
+    (b'\'', 20),
+    (b'"', 20),
+    (b'-', 15),
+    (b'j', 10),
+    (b'x', 10),
+    (b'q', 8),
+    (b'z', 6),
+    (b'?', 5),
+    (b'!', 5),
+    (b';', 3),
+    (b':', 3),
+    (b'(', 2),
+];
+
+/// Canonical codes of at most 15 bits for [`ENGLISH_FREQUENCIES`], uppercase
+/// letters weighing a sixteenth of their lowercase form. Every other byte
+/// still gets a code, so any data can be encoded. The codes only depend on
+/// the constant, so they can be rebuilt on both sides of a connection.
+pub fn english_codes() -> HuffmanCode {
+    let mut weights = (0..=255).map(|c| (c, 0)).collect::<Map<u8, usize>>();
+    for (c, frequency) in ENGLISH_FREQUENCIES {
+        weights.insert(c, frequency);
+        if c.is_ascii_lowercase() {
+            weights.insert(c.to_ascii_uppercase(), frequency / 16);
+        }
+    }
+    let Ok(codes) = length_limited_codes(&weights, 15) else {
+        unreachable!("256 characters fit in 15 bit codes");
+    };
+    codes
+}
+
+/// Encodes `data` with a table shared ahead of time, such as
+/// [`english_codes`]. The output is only the packed bitstream and its length
+/// in bits; no table or header is added.
+pub fn encode_static(data: &[u8], codes: &HuffmanCode) -> Result<(Vec<u8>, usize), EncodeError> {
+    huffman_encode_with_codes(data, codes)
+}
+
+/// Decodes `len` bytes written by [`encode_static`] with the same `codes`.
+pub fn decode_static(
+    encoded: &[u8],
+    codes: &HuffmanCode,
+    len: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    decode_bits(BitReader::new(encoded), &build_decode_tree(codes), len)
+}
+
+/// Decodes `count` bytes from a bitstream that carries no header, with the
+/// tree taken from a `model` both sides share, e.g. one built once by
+/// [`huffman_build`](crate::huffman_build) and kept in configuration.
+pub fn decompress_with_model(
+    bits: &[u8],
+    model: &HuffmanModel,
+    count: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    model.decode(bits, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{huffman_build, validate_prefix_free};
+
+    #[test]
+    fn test_english_codes() {
+        let codes = english_codes();
+        assert_eq!(codes.len(), 256);
+        assert!(validate_prefix_free(&codes).is_ok());
+        assert!(codes[&b' '].len() <= codes[&b'e'].len());
+        assert!(codes[&b'e'].len() < codes[&b'E'].len());
+        assert!(codes.values().all(|code| code.len() <= 15));
+        assert_eq!(english_codes(), codes);
+    }
+
+    #[test]
+    fn test_encode_static() {
+        let codes = english_codes();
+        let text = b"The quick brown fox jumps over the lazy dog.\n";
+        let (encoded, bit_len) = encode_static(text, &codes).unwrap();
+        let bits = text.iter().map(|c| codes[c].len()).sum::<usize>();
+        // Nothing but the codes themselves.
+        assert_eq!(bit_len, bits);
+        assert_eq!(encoded.len(), bits.div_ceil(8));
+        assert!(encoded.len() < text.len());
+        assert_eq!(decode_static(&encoded, &codes, text.len()).unwrap(), text);
+
+        let binary = [0x00, 0xff, 0x80];
+        let (encoded, _) = encode_static(&binary, &codes).unwrap();
+        assert_eq!(decode_static(&encoded, &codes, 3).unwrap(), binary);
+    }
+
+    #[test]
+    fn test_decompress_with_model() {
+        let model = huffman_build(b"this is an example of a huffman tree");
+        let message = b"a fine tree";
+        let (bits, _) = model.encode(message).unwrap();
+        // Only the bitstream travels; the model is supplied separately.
+        assert!(bits.len() < message.len());
+        assert_eq!(
+            decompress_with_model(&bits, &model, message.len()).unwrap(),
+            message
+        );
+        assert!(matches!(
+            decompress_with_model(&bits, &model, 100),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    frequency_counter, frequency_list, huffman_codes, huffman_tree, Histogram, HuffmanCode,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeStats {
+    /// Size of the input in bytes.
+    pub original_len: usize,
+    /// Size of the packed output in bytes, including padding.
+    pub encoded_len: usize,
+    /// Number of meaningful bits in the packed output.
+    pub encoded_bits: usize,
+    /// Encoded bits over original bits; lower is better.
+    pub ratio: f64,
+    /// Shannon entropy of the input in bits per byte.
+    pub entropy: f64,
+}
+
+/// Shannon entropy `-Σ p·log2(p)` of the histogram in bits per byte, the lower
+/// bound for the average code length of any prefix code over it. Huffman
+/// codes always come within one bit of it.
+pub fn entropy(counter: &HashMap<u8, usize>) -> f64 {
+    let total = counter.values().sum::<usize>() as f64;
+    -counter
+        .values()
+        .map(|&f| f as f64 / total)
+        .map(|p| p * p.log2())
+        .sum::<f64>()
+}
+
+/// Mean code length in bits per byte, weighting each code by its frequency in
+/// `counter`; 0 for an empty histogram. Panics if a byte of `counter` has no
+/// code.
+pub fn average_code_length(counter: &HashMap<u8, usize>, codes: &HuffmanCode) -> f64 {
+    let total = counter.values().sum::<usize>();
+    if total == 0 {
+        return 0.0;
+    }
+    let bits = counter
+        .iter()
+        .map(|(c, &f)| f * codes[c].len())
+        .sum::<usize>();
+    bits as f64 / total as f64
+}
+
+/// How many bits per byte `codes` spend above the [`entropy`] of `counter`.
+/// Never negative, and below 1 for Huffman codes built from `counter`.
+pub fn redundancy(counter: &HashMap<u8, usize>, codes: &HuffmanCode) -> f64 {
+    average_code_length(counter, codes) - entropy(counter)
+}
+
+/// Bits each byte saves over its 8-bit form across all its occurrences,
+/// `(8 - code length) * frequency`. Bytes whose code is longer than 8 bits
+/// cost bits instead and get a negative value. Panics if a byte of `counter`
+/// has no code.
+pub fn per_symbol_savings(counter: &HashMap<u8, usize>, codes: &HuffmanCode) -> HashMap<u8, i64> {
+    counter
+        .iter()
+        .map(|(&c, &f)| (c, (8 - codes[&c].len() as i64) * f as i64))
+        .collect()
+}
+
+/// Measures how well `data` compresses without producing the bitstream.
+/// Empty input has a ratio of 1.0 since nothing is gained or lost.
+pub fn encode_stats(data: &[u8]) -> EncodeStats {
+    if data.is_empty() {
+        return EncodeStats {
+            original_len: 0,
+            encoded_len: 0,
+            encoded_bits: 0,
+            ratio: 1.0,
+            entropy: 0.0,
+        };
+    }
+
+    let counter = frequency_counter(data);
+    let codes = huffman_codes(&huffman_tree(&frequency_list(&counter)));
+    let encoded_bits = counter
+        .iter()
+        .map(|(c, f)| f * codes[c].len())
+        .sum::<usize>();
+    EncodeStats {
+        original_len: data.len(),
+        encoded_len: encoded_bits.div_ceil(8),
+        encoded_bits,
+        ratio: encoded_bits as f64 / (data.len() * 8) as f64,
+        entropy: entropy(&counter),
+    }
+}
+
+/// Size in bytes of the packed output of [`huffman_encode`](crate::huffman_encode)
+/// for `data`, computed from the code lengths alone.
+pub fn estimated_encoded_bytes(data: &[u8]) -> usize {
+    encode_stats(data).encoded_len
+}
+
+/// Encoded bits over original bits for `original`; see [`encode_stats`].
+pub fn compression_ratio(original: &[u8]) -> f64 {
+    encode_stats(original).ratio
+}
+
+/// Table of the bytes in `data`, most frequent first, with each byte in hex
+/// and as a character (`.` if not printable), its count, its share of the
+/// data and its Huffman code.
+///
+/// ```text
+/// byte  char  count  percent  code
+/// 0x41  A         3    42.9%  1
+/// ```
+pub fn frequency_report(data: &[u8]) -> String {
+    let histogram = Histogram::from_bytes(data);
+    let mut report = String::from("byte  char  count  percent  code\n");
+    if data.is_empty() {
+        return report;
+    }
+    let codes = huffman_codes(&huffman_tree(&histogram.iter().collect::<Vec<_>>()));
+    for (c, count) in histogram.iter() {
+        let char = if c.is_ascii_graphic() || c == b' ' {
+            c as char
+        } else {
+            '.'
+        };
+        let percent = 100.0 * count as f64 / histogram.total() as f64;
+        let code = std::str::from_utf8(&codes[&c]).unwrap_or_default();
+        let _ = writeln!(
+            report,
+            "0x{c:02X}  {char:<4}  {count:>5}  {percent:>6.1}%  {code}"
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::huffman_encode_bytes;
+
+    fn codes_for(counter: &HashMap<u8, usize>) -> HuffmanCode {
+        huffman_codes(&huffman_tree(&frequency_list(counter)))
+    }
+
+    #[test]
+    fn test_entropy_uniform() {
+        let counter = frequency_counter(b"ABCDABCDABCD");
+        assert_eq!(entropy(&counter), 2.0);
+        assert_eq!(average_code_length(&counter, &codes_for(&counter)), 2.0);
+        assert_eq!(redundancy(&counter, &codes_for(&counter)), 0.0);
+    }
+
+    #[test]
+    fn test_entropy_bound() {
+        let counter = frequency_counter(b"AAAAAAAAAABBBBBCCCDDEFGH");
+        let entropy = entropy(&counter);
+        let codes = codes_for(&counter);
+        let average = average_code_length(&counter, &codes);
+        assert!(entropy <= average);
+        assert!(average < entropy + 1.0);
+        assert_eq!(redundancy(&counter, &codes), average - entropy);
+        assert_eq!(super::entropy(&frequency_counter(b"AAAA")), 0.0);
+    }
+
+    #[test]
+    fn test_average_code_length() {
+        // A=1, B=00, C=010, D=011.
+        let counter = frequency_counter(b"AABCBAD");
+        assert_eq!(
+            average_code_length(&counter, &codes_for(&counter)),
+            13.0 / 7.0
+        );
+        let fixed = (b'A'..=b'D').map(|c| (c, b"00".to_vec())).collect();
+        assert_eq!(average_code_length(&counter, &fixed), 2.0);
+        assert_eq!(average_code_length(&HashMap::new(), &fixed), 0.0);
+    }
+
+    #[test]
+    fn test_per_symbol_savings() {
+        // A=1, B=00, C=010, D=011.
+        let counter = frequency_counter(b"AABCBAD");
+        let savings = per_symbol_savings(&counter, &codes_for(&counter));
+        let expected = [(b'A', 21), (b'B', 12), (b'C', 5), (b'D', 5)];
+        assert_eq!(savings, expected.into_iter().collect());
+
+        // Fibonacci frequencies give the two rarest bytes 15 bit codes.
+        let (mut a, mut b) = (1, 1);
+        let mut counter = HashMap::new();
+        for c in b'A'..=b'P' {
+            counter.insert(c, a);
+            (a, b) = (b, a + b);
+        }
+        let savings = per_symbol_savings(&counter, &codes_for(&counter));
+        assert_eq!(savings[&b'P'], 7 * 987);
+        assert_eq!(savings[&b'A'], -7);
+        assert_eq!(savings[&b'B'], -7);
+        assert!(savings.values().sum::<i64>() > 0);
+    }
+
+    #[test]
+    fn test_estimated_encoded_bytes() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..1000).map(|_| r.gen_range(0..32)).collect::<Vec<u8>>();
+        let inputs: [&[u8]; 5] = [b"A", b"AAAAAAAAA", b"AABCBAD", b"ABCDEFGH", &random];
+        for data in inputs {
+            let (encoded, _, _) = huffman_encode_bytes(data);
+            assert_eq!(estimated_encoded_bytes(data), encoded.len());
+        }
+        assert_eq!(estimated_encoded_bytes(b""), 0);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let ratio = compression_ratio(b"AAAAAAAB");
+        assert_eq!(ratio, 0.125);
+        assert!(ratio < 0.5);
+    }
+
+    #[test]
+    fn test_encode_stats() {
+        let data = b"AABCBAD";
+        let (encoded, _, bit_len) = huffman_encode_bytes(data);
+        let stats = encode_stats(data);
+        assert_eq!(stats.original_len, 7);
+        assert_eq!(stats.encoded_len, encoded.len());
+        assert_eq!(stats.encoded_bits, bit_len);
+        assert_eq!(stats.ratio, 13.0 / 56.0);
+        assert!(stats.entropy > 1.0 && stats.entropy < 13.0 / 7.0);
+
+        assert_eq!(encode_stats(b"").ratio, 1.0);
+    }
+
+    #[test]
+    fn test_frequency_report() {
+        // A=1, B=00, C=010, D=011.
+        assert_eq!(
+            frequency_report(b"AABCBAD"),
+            "byte  char  count  percent  code\n\
+             0x41  A         3    42.9%  1\n\
+             0x42  B         2    28.6%  00\n\
+             0x43  C         1    14.3%  010\n\
+             0x44  D         1    14.3%  011\n"
+        );
+        assert!(frequency_report(b"\n\n ").contains("0x0A  .         2    66.7%  "));
+        assert_eq!(frequency_report(b""), "byte  char  count  percent  code\n");
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    bitio::BitWriter, build_decode_tree, decode_step, format::invalid_data, pack_code, EncodeError,
+    HuffmanCode, HuffmanTreeNode,
+};
+
+/// Counts character frequencies like `frequency_counter`, reading `reader` in
+/// fixed-size chunks instead of requiring the whole input in memory.
+pub fn frequency_counter_reader<R: Read>(reader: &mut R) -> io::Result<HashMap<u8, usize>> {
+    let mut counter = HashMap::new();
+    let mut chunk = [0; 8 * 1024];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(counter),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &c in &chunk[..read] {
+            *counter.entry(c).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Encodes everything written to it with a fixed code table and writes the
+/// packed bits to `inner`, using the same layout as `huffman_encode`.
+///
+/// Bits that do not fill a whole byte are kept between calls to `write`, so
+/// [`HuffmanWriter::finish`] must be called to write the padded last byte.
+pub struct HuffmanWriter<W: Write> {
+    inner: W,
+    codes: [Option<(u32, u8)>; 256],
+    // Codes too long to pack into `codes`, which only pathological
+    // frequencies produce, written bit by bit.
+    long_codes: HuffmanCode,
+    bits: BitWriter,
+}
+
+impl<W: Write> HuffmanWriter<W> {
+    pub fn new(inner: W, codes: &HuffmanCode) -> Self {
+        let mut table = [None; 256];
+        let mut long_codes = HuffmanCode::new();
+        for (&c, code) in codes {
+            if code.len() <= 32 {
+                table[c as usize] = Some(pack_code(code));
+            } else {
+                long_codes.insert(c, code.clone());
+            }
+        }
+        HuffmanWriter {
+            inner,
+            codes: table,
+            long_codes,
+            bits: BitWriter::new(),
+        }
+    }
+
+    // Buffers the code of `c`, or returns false if it has none.
+    fn write_code(&mut self, c: u8) -> bool {
+        if let Some((code, length)) = self.codes[c as usize] {
+            self.bits.write_bits(code as u64, length);
+        } else if let Some(code) = self.long_codes.get(&c) {
+            for &bit in code {
+                self.bits.write_bit(bit == b'1');
+            }
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Number of encoded bits written so far, excluding padding.
+    pub fn bit_len(&self) -> usize {
+        self.bits.bit_len()
+    }
+
+    /// Writes the remaining bits padded with zeros and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let (last_bytes, _) = self.bits.finish();
+        self.inner.write_all(&last_bytes)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for HuffmanWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        for c in buf {
+            if !self.write_code(*c) {
+                if written == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        EncodeError::NoCodeForSymbol(*c),
+                    ));
+                }
+                break;
+            }
+            written += 1;
+        }
+        self.inner.write_all(&self.bits.take_bytes())?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decodes `len` characters from the packed bits read from `inner`, walking
+/// the tree [`build_decode_tree`] rebuilds from the codes one edge per bit.
+///
+/// The inner reader is consumed one byte at a time, so wrap unbuffered
+/// sources in a `BufReader`. A code split across two bytes, or across two
+/// calls to `read`, is carried over in the reader's state.
+pub struct HuffmanReader<R: Read> {
+    inner: R,
+    tree: HuffmanTreeNode,
+    // Bits of the code read so far, the path from the root to the node the
+    // next `read` resumes from.
+    cursor: Vec<bool>,
+    byte: u8,
+    bits_left: u8,
+    remaining: usize,
+}
+
+impl<R: Read> HuffmanReader<R> {
+    pub fn new(inner: R, codes: &HuffmanCode, len: usize) -> Self {
+        HuffmanReader {
+            inner,
+            tree: build_decode_tree(codes),
+            cursor: Vec::new(),
+            byte: 0,
+            bits_left: 0,
+            remaining: len,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for HuffmanReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        let mut node = &self.tree;
+        for &bit in &self.cursor {
+            decode_step(&self.tree, &mut node, bit).map_err(invalid_data)?;
+        }
+        while read < buf.len() && self.remaining > 0 {
+            if self.bits_left == 0 {
+                let mut byte = [0];
+                match self.inner.read_exact(&mut byte) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && read > 0 => break,
+                    Err(e) => return Err(e),
+                }
+                self.byte = byte[0];
+                self.bits_left = 8;
+            }
+            self.bits_left -= 1;
+            let bit = self.byte >> self.bits_left & 1 == 1;
+            if let Some(c) = decode_step(&self.tree, &mut node, bit).map_err(invalid_data)? {
+                buf[read] = c;
+                read += 1;
+                self.remaining -= 1;
+                self.cursor.clear();
+            } else {
+                self.cursor.push(bit);
+            }
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        encode_bits, frequency_counter, frequency_list, huffman_codes, huffman_tree, pack_bits,
+        DecodeError,
+    };
+
+    fn codes_for(data: &[u8]) -> HuffmanCode {
+        huffman_codes(&huffman_tree(&frequency_list(&frequency_counter(data))))
+    }
+
+    #[test]
+    fn test_frequency_counter_reader() {
+        let data = StdRng::seed_from_u64(42)
+            .sample_iter(&Alphanumeric)
+            .take(1024 * 20)
+            .collect::<Vec<u8>>();
+        let result = frequency_counter_reader(&mut data.as_slice()).unwrap();
+        assert_eq!(result, frequency_counter(&data));
+        assert!(frequency_counter_reader(&mut io::empty())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_huffman_writer() {
+        let data = b"AABCBAD";
+        let codes = codes_for(data);
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        writer.write_all(data).unwrap();
+        assert_eq!(writer.bit_len(), 13);
+        assert_eq!(writer.finish().unwrap(), vec![0b11000100, 0b01011000]);
+    }
+
+    #[test]
+    fn test_huffman_writer_chunked() {
+        let data = StdRng::seed_from_u64(42)
+            .sample_iter(&Alphanumeric)
+            .take(4096)
+            .collect::<Vec<u8>>();
+        let codes = codes_for(&data);
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        writer.write_all(&data).unwrap();
+        let single = writer.finish().unwrap();
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        for chunk in data.chunks(3) {
+            writer.write_all(chunk).unwrap();
+        }
+        let chunked = writer.finish().unwrap();
+
+        assert_eq!(single, chunked);
+        assert_eq!(single, pack_bits(&encode_bits(&data, &codes)));
+    }
+
+    #[test]
+    fn test_huffman_writer_missing_code() {
+        let codes = codes_for(b"AB");
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        assert_eq!(writer.write(b"ABC").unwrap(), 2);
+        let error = writer.write(b"C").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_huffman_writer_long_codes() {
+        // Codes too long to pack are written bit by bit.
+        let codes = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', vec![b'1'; 40])]);
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        writer.write_all(b"ABA").unwrap();
+        writer.write_all(b"B").unwrap();
+        assert_eq!(writer.bit_len(), 82);
+        assert_eq!(
+            writer.finish().unwrap(),
+            pack_bits(&encode_bits(b"ABAB", &codes))
+        );
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        assert_eq!(writer.write(b"ABC").unwrap(), 2);
+        assert!(writer.write(b"C").is_err());
+    }
+
+    #[test]
+    fn test_huffman_reader() {
+        let data = StdRng::seed_from_u64(42)
+            .sample_iter(&Alphanumeric)
+            .take(1024 * 20)
+            .collect::<Vec<u8>>();
+        let codes = codes_for(&data);
+        let mut writer = HuffmanWriter::new(Vec::new(), &codes);
+        writer.write_all(&data).unwrap();
+        let encoded = writer.finish().unwrap();
+
+        let inner = BufReader::with_capacity(1, encoded.as_slice());
+        let mut reader = HuffmanReader::new(inner, &codes, data.len());
+        let mut decoded = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                n => decoded.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_huffman_reader_truncated() {
+        let codes = codes_for(b"AABCBAD");
+        let mut reader = HuffmanReader::new(&[0b11000100][..], &codes, 7);
+        let mut decoded = Vec::new();
+        let error = reader.read_to_end(&mut decoded).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(decoded, b"AABC");
+    }
+
+    fn read_one_at_a_time<R: Read>(mut reader: HuffmanReader<R>) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        let mut buf = [0];
+        while reader.read(&mut buf)? > 0 {
+            decoded.push(buf[0]);
+        }
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_huffman_reader_tree_walk() {
+        // Codes longer than a byte resume mid-code on the next `read`.
+        let codes = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', vec![b'1'; 40])]);
+        let encoded = pack_bits(&encode_bits(b"ABAB", &codes));
+        let reader = HuffmanReader::new(&encoded[..], &codes, 4);
+        assert_eq!(read_one_at_a_time(reader).unwrap(), b"ABAB");
+
+        // A single leaf has the code `1`.
+        let codes = codes_for(b"AAA");
+        let reader = HuffmanReader::new(&[0b1110_0000][..], &codes, 3);
+        assert_eq!(read_one_at_a_time(reader).unwrap(), b"AAA");
+
+        // No code starts with 11.
+        let codes = HuffmanCode::from([(b'A', b"0".to_vec()), (b'B', b"10".to_vec())]);
+        let reader = HuffmanReader::new(&[0b0101_1000][..], &codes, 4);
+        let error = read_one_at_a_time(reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            *error
+                .into_inner()
+                .unwrap()
+                .downcast::<DecodeError>()
+                .unwrap(),
+            DecodeError::InvalidCode
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+// Reversible transforms applied to the data before Huffman coding, so the
+// coder sees a more skewed histogram.
+
+use alloc::vec::Vec;
+use core::iter::repeat_n;
+
+use crate::DecodeError;
+
+// Longest run `rle_encode` writes as one group: 4 copies plus a count byte.
+const MAX_RUN: usize = 4 + u8::MAX as usize;
+
+/// Run-length transform: a run of 4 or more equal bytes is written as 4
+/// copies followed by a byte holding how many more copies follow. Longer runs
+/// than fit in the count start a new group.
+pub(crate) fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while let Some(&c) = rest.first() {
+        let run = rest.iter().take(MAX_RUN).take_while(|&&b| b == c).count();
+        if run >= 4 {
+            encoded.extend([c; 4]);
+            encoded.push((run - 4) as u8);
+        } else {
+            encoded.extend(repeat_n(c, run));
+        }
+        rest = &rest[run..];
+    }
+    encoded
+}
+
+/// Inverse of [`rle_encode`]. Fails if the data ends where a count byte is
+/// expected.
+pub(crate) fn rle_decode(encoded: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let mut data = Vec::with_capacity(encoded.len());
+    let mut bytes = encoded.iter();
+    let mut run = 0;
+    while let Some(&c) = bytes.next() {
+        run = if data.last() == Some(&c) { run + 1 } else { 1 };
+        data.push(c);
+        if run == 4 {
+            let &extra = bytes.next().ok_or(DecodeError::UnexpectedEof)?;
+            data.extend(repeat_n(c, extra as usize));
+            // The next byte starts a new group even if it is `c` again.
+            run = 0;
+        }
+    }
+    Ok(data)
+}
+
+/// Move-to-front transform: each byte is replaced by its position in a list
+/// of all 256 byte values, and then moved to the front of the list. Recently
+/// seen bytes come out as small numbers, so data whose bytes cluster locally
+/// turns into mostly low values.
+pub fn mtf_encode(data: &[u8]) -> Vec<u8> {
+    let mut order = identity();
+    data.iter()
+        .map(|&c| {
+            let Some(index) = order.iter().position(|&b| b == c) else {
+                unreachable!("The list holds every byte");
+            };
+            order[..=index].rotate_right(1);
+            index as u8
+        })
+        .collect()
+}
+
+/// Inverse of [`mtf_encode`].
+pub fn mtf_decode(encoded: &[u8]) -> Vec<u8> {
+    let mut order = identity();
+    encoded
+        .iter()
+        .map(|&index| {
+            let index = index as usize;
+            let c = order[index];
+            order[..=index].rotate_right(1);
+            c
+        })
+        .collect()
+}
+
+fn identity() -> [u8; 256] {
+    core::array::from_fn(|i| i as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::Histogram;
+
+    #[test]
+    fn test_rle_encode() {
+        assert_eq!(rle_encode(b"AAAAAAAAAAB"), b"AAAA\x06B");
+        assert_eq!(rle_encode(b"AAABBBBCC"), b"AAABBBB\x00CC");
+        assert_eq!(rle_encode(b""), b"");
+
+        let long_run = b"A".repeat(MAX_RUN + 5);
+        assert_eq!(rle_encode(&long_run), b"AAAA\xffAAAA\x01");
+    }
+
+    #[test]
+    fn test_rle_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let runs = (0..200)
+            .flat_map(|_| repeat_n(r.gen_range(b'A'..=b'C'), r.gen_range(1..300)))
+            .collect::<Vec<u8>>();
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        for data in [&b""[..], b"AAAA", b"AAAAA", &runs, &random] {
+            assert_eq!(rle_decode(&rle_encode(data)).unwrap(), data);
+        }
+        assert!(matches!(
+            rle_decode(b"AAAA"),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_mtf_encode() {
+        assert_eq!(mtf_encode(b"AAAB"), [b'A', 0, 0, b'B']);
+        assert_eq!(mtf_encode(b"BABA"), [b'B', b'A' + 1, 1, 1]);
+        assert_eq!(mtf_encode(b""), b"");
+    }
+
+    #[test]
+    fn test_mtf_round_trip() {
+        let mut r = StdRng::seed_from_u64(42);
+        let random = (0..4096).map(|_| r.gen::<u8>()).collect::<Vec<u8>>();
+        let all_bytes = (0..=255).rev().collect::<Vec<u8>>();
+        for data in [&b""[..], b"AABCBAD", &all_bytes, &random] {
+            assert_eq!(mtf_decode(&mtf_encode(data)), data);
+        }
+    }
+
+    #[test]
+    fn test_mtf_skews_histogram() {
+        // Short runs of a few bytes each, switching between many bytes: the
+        // input is spread evenly, the output is mostly zeros.
+        let mut r = StdRng::seed_from_u64(42);
+        let data = (0..500)
+            .flat_map(|_| repeat_n(r.gen_range(b'a'..=b'z'), r.gen_range(2..6)))
+            .collect::<Vec<u8>>();
+        let before = Histogram::from_bytes(&data);
+        let after = Histogram::from_bytes(&mtf_encode(&data));
+        assert_eq!(after.most_frequent(), Some(0));
+        assert!(after.count(0) > 2 * data.len() / 3);
+        let (_, most_before) = before.iter().next().unwrap();
+        assert!(most_before < data.len() / 10);
+    }
+}